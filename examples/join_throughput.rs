@@ -0,0 +1,124 @@
+// 参加ボタンが同時に押されたときのスループットを、接続の共有方法ごとに計測する
+//   before: Mutexで囲んだ RedisClient をロックしたまま、参加者を読み出してから書き込む (以前の実装)
+//   after:  クローンした ConnectionManager で、読み出しと書き込みをLuaスクリプトでまとめて行う (現在の実装)
+//
+// REDIS_URL (既定: redis://127.0.0.1/) のRedisに valo_bench:* のキーを作成し、終了時に削除する
+//   cargo run --release --example join_throughput -- [同時に参加するユーザー数] [繰り返し回数]
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use redis::{aio::ConnectionManager, AsyncTypedCommands, Script};
+use tokio::sync::Mutex;
+
+const JOIN_SCRIPT: &str = include_str!("../src/bot/buttons/join.lua");
+const CREATOR: u64 = 1;
+
+struct Sample {
+  elapsed: Duration,
+  // 同時に書き込んで消えた参加者がいないか確かめる
+  joined: usize,
+}
+
+fn keys(round: usize) -> (String, String) {
+  (format!("valo_bench:{}:recruit", round), format!("valo_bench:{}:has_slots", round))
+}
+
+async fn setup(conn: &mut ConnectionManager, round: usize) -> redis::RedisResult<()> {
+  let (recruit, has_slots) = keys(round);
+  conn.hset_multiple(&recruit, &[("creator", CREATOR.to_string()), ("joined", CREATOR.to_string())]).await?;
+  conn.sadd(&has_slots, round).await?;
+  Ok(())
+}
+
+async fn joined(conn: &mut ConnectionManager, round: usize) -> redis::RedisResult<usize> {
+  let (recruit, has_slots) = keys(round);
+  let joined = conn.hget(&recruit, "joined").await?.unwrap_or_default();
+  conn.del(&[recruit, has_slots]).await?;
+  Ok(joined.split(',').filter(|u| !u.is_empty()).count())
+}
+
+async fn before(conn: ConnectionManager, users: u64, round: usize) -> redis::RedisResult<Sample> {
+  let (recruit, _) = keys(round);
+  let conn = Arc::new(Mutex::new(conn));
+  let start = Instant::now();
+  let tasks = (0..users).map(|i| {
+    let conn = conn.clone();
+    let recruit = recruit.clone();
+    tokio::spawn(async move {
+      let user = (CREATOR + 1 + i).to_string();
+      let mut conn = conn.lock().await;
+      let joined = conn.hget(&recruit, "joined").await?.unwrap_or_default();
+      if joined.split(',').any(|u| u == user) {
+        return Ok(());
+      }
+      let joined = if joined.is_empty() { user } else { format!("{},{}", joined, user) };
+      conn.hset(&recruit, "joined", joined).await?;
+      redis::RedisResult::Ok(())
+    })
+  });
+  for task in futures::future::join_all(tasks).await {
+    task.expect("join task panicked")?;
+  }
+  let elapsed = start.elapsed();
+  let mut conn = conn.lock().await.clone();
+  Ok(Sample { elapsed, joined: joined(&mut conn, round).await? })
+}
+
+async fn after(conn: ConnectionManager, users: u64, round: usize) -> redis::RedisResult<Sample> {
+  let (recruit, has_slots) = keys(round);
+  let start = Instant::now();
+  let tasks = (0..users).map(|i| {
+    let mut conn = conn.clone();
+    let (recruit, has_slots) = (recruit.clone(), has_slots.clone());
+    tokio::spawn(async move {
+      Script::new(JOIN_SCRIPT)
+        .key(recruit)
+        .key(has_slots)
+        .arg(CREATOR + 1 + i)
+        .arg(users + 1)
+        .arg(round)
+        .invoke_async::<i64>(&mut conn)
+        .await
+    })
+  });
+  for task in futures::future::join_all(tasks).await {
+    task.expect("join task panicked")?;
+  }
+  let elapsed = start.elapsed();
+  let mut conn = conn.clone();
+  Ok(Sample { elapsed, joined: joined(&mut conn, round).await? })
+}
+
+fn print(name: &str, users: u64, results: &[Sample]) {
+  let elapsed: Duration = results.iter().map(|r| r.elapsed).sum();
+  let joins = users as f64 * results.len() as f64;
+  let lost: usize = results.iter().map(|r| (users as usize + 1).saturating_sub(r.joined)).sum();
+  println!(
+    "{:<6} {:>10.1} joins/s  平均 {:>8.2} ms/回  消えた参加者 {}",
+    name,
+    joins / elapsed.as_secs_f64(),
+    elapsed.as_secs_f64() * 1000.0 / results.len() as f64,
+    lost,
+  );
+}
+
+#[tokio::main]
+async fn main() -> redis::RedisResult<()> {
+  let mut args = std::env::args().skip(1);
+  let users: u64 = args.next().and_then(|a| a.parse().ok()).unwrap_or(100);
+  let rounds: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(20);
+  let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_string());
+  let mut conn = ConnectionManager::new(redis::Client::open(url)?).await?;
+  println!("同時に参加するユーザー {} 人 × {} 回", users, rounds);
+  let mut before_results = Vec::new();
+  let mut after_results = Vec::new();
+  // 交互に実行して、Redis側の状態の変化による偏りを減らす
+  for round in 0..rounds {
+    setup(&mut conn, round * 2).await?;
+    before_results.push(before(conn.clone(), users, round * 2).await?);
+    setup(&mut conn, round * 2 + 1).await?;
+    after_results.push(after(conn.clone(), users, round * 2 + 1).await?);
+  }
+  print("before", users, &before_results);
+  print("after", users, &after_results);
+  Ok(())
+}
@@ -1,291 +1,195 @@
+pub mod admin_log;
 pub mod buttons;
+pub mod commands;
+pub mod components;
+pub mod custom_id;
 pub mod questions;
 pub mod types;
 pub mod colors;
+pub mod locale;
+pub mod migration;
 pub mod panels;
 
 use serenity::{
-  all::{ActionRowComponent, ChannelId, ComponentInteraction, ComponentInteractionDataKind, Context, CreateInteractionResponse, CreateInteractionResponseMessage, EventHandler, Interaction, Message, Ready, UserId},
+  all::{ComponentInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage, EventHandler, Interaction, Message, Ready, ResumedEvent, ShardStageUpdateEvent},
+  gateway::ConnectionStage,
   async_trait,
 };
-use std::{collections::HashMap, str::FromStr, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::Mutex;
+use tracing::{Instrument, Span};
 use types::WebhookData;
 
-use crate::{bot::{buttons::{DeleteResponse, LeaveResponse}, types::{ApServer, Member, Mode, Rank, RedisClient, WebhookDataExt}}, config};
+use crate::{bot::{admin_log::{AdminLog, ErrorContext}, custom_id::SessionId, locale::Text, panels::EntryDebounce, types::RedisClient}, config::Config, health::GatewayState, shutdown::Shutdown};
 
 #[derive(Clone)]
 pub struct Handler {
-  pub question_state: Arc<Mutex<HashMap<UserId, WebhookData>>>,
-  pub component_store: Arc<Mutex<HashMap<UserId, ComponentInteraction>>>,
-  pub redis_client: Arc<Mutex<RedisClient>>,
+  // 募集作成フローの状態。セッションごとに持つ
+  pub question_state: Arc<Mutex<HashMap<SessionId, WebhookData>>>,
+  pub component_store: Arc<Mutex<HashMap<SessionId, ComponentInteraction>>>,
+  pub redis_client: RedisClient,
+  pub config: Arc<Config>,
+  pub gateway: Arc<GatewayState>,
+  pub shutdown: Arc<Shutdown>,
+  pub admin_log: Arc<AdminLog>,
+  pub entry_debounce: Arc<EntryDebounce>,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
-  async fn ready(&self, _: Context, ready: Ready) {
+  async fn ready(&self, ctx: Context, ready: Ready) {
     tracing::info!("{} is ready", ready.user.name);
+    self.gateway.set_connected(true);
+    if let Err(e) = commands::register(&ctx.http).await {
+      e.log("Failed to register application commands");
+    }
+  }
+  async fn resume(&self, _: Context, _: ResumedEvent) {
+    tracing::info!("Gateway session resumed");
+    self.gateway.set_connected(true);
+  }
+  async fn shard_stage_update(&self, _: Context, event: ShardStageUpdateEvent) {
+    tracing::info!(old = %event.old, new = %event.new, "Shard stage updated");
+    self.gateway.set_connected(event.new == ConnectionStage::Connected);
   }
   async fn message(&self, ctx: Context, msg: Message) {
-    let channel = match config::get("CHANNEL_ID") {
-      Ok(id) => match ChannelId::from_str(&id) {
-        Ok(id) => id,
-        Err(e) => {
-          tracing::warn!(error = %e, "Failed to parse CHANNEL_ID");
-          return;
-        }
+    let Some(_in_flight) = self.shutdown.enter() else {
+      return;
+    };
+    let span = tracing::info_span!(
+      "message",
+      guild = msg.guild_id.map(|g| g.get()),
+      user = msg.author.id.get(),
+      message = msg.id.get(),
+    );
+    self.handle_message(ctx, msg).instrument(span).await;
+  }
+  async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+    let Some(_in_flight) = self.shutdown.enter() else {
+      self.reject_interaction(&ctx, &interaction).await;
+      return;
+    };
+    let span = interaction_span(&interaction);
+    self.handle_interaction(ctx, interaction).instrument(span).await;
+  }
+}
+
+// インタラクションごとのログに共通で付けるフィールド
+fn interaction_span(interaction: &Interaction) -> Span {
+  let (id, guild, user, custom_id, command, message) = match interaction {
+    Interaction::Component(component) => (
+      component.id,
+      component.guild_id,
+      component.user.id,
+      Some(component.data.custom_id.as_str()),
+      None,
+      Some(component.message.id),
+    ),
+    Interaction::Command(command) => (command.id, command.guild_id, command.user.id, None, Some(command.data.name.as_str()), None),
+    Interaction::Modal(modal) => (
+      modal.id,
+      modal.guild_id,
+      modal.user.id,
+      Some(modal.data.custom_id.as_str()),
+      None,
+      modal.message.as_ref().map(|m| m.id),
+    ),
+    _ => return tracing::info_span!("interaction"),
+  };
+  tracing::info_span!(
+    "interaction",
+    // エラーの返信と管理者用チャンネルに出す相関ID
+    interaction = id.get(),
+    guild = guild.map(|g| g.get()),
+    user = user.get(),
+    custom_id,
+    command,
+    message = message.map(|m| m.get()),
+  )
+}
+
+impl Handler {
+  // 停止処理中に届いたインタラクションには再起動中であることだけを返す
+  async fn reject_interaction(&self, ctx: &Context, interaction: &Interaction) {
+    let response = |locale| CreateInteractionResponse::Message(
+      CreateInteractionResponseMessage::new()
+        .content(Text::ShuttingDown.get(locale))
+        .ephemeral(true)
+    );
+    let result = match interaction {
+      Interaction::Component(component) => {
+        let locale = self.locale(component.guild_id, Some(&component.locale)).await;
+        component.create_response(&ctx.http, response(locale)).await
+      }
+      Interaction::Command(command) => {
+        let locale = self.locale(command.guild_id, Some(&command.locale)).await;
+        command.create_response(&ctx.http, response(locale)).await
       }
-      Err(e) => {
-        tracing::warn!(error = %e, "Failed to get CHANNEL_ID from environment variables");
-        return;
+      Interaction::Modal(modal) => {
+        let locale = self.locale(modal.guild_id, Some(&modal.locale)).await;
+        modal.create_response(&ctx.http, response(locale)).await
       }
+      _ => Ok(()),
     };
-    if msg.channel_id != channel {
+    if let Err(e) = result {
+      tracing::warn!(error = %e, "Failed to reject interaction during shutdown");
+    }
+  }
+  // 募集の作成、参加、削除等のあとに募集作成パネルの一覧を更新する
+  pub async fn refresh_entry(&self, ctx: &Context) {
+    let locale = self.locale(Some(self.redis_client.guild), None).await;
+    if let Err(e) = panels::refresh_entry(&ctx.http, &self.config, &self.redis_client, locale).await {
+      e.log("Failed to refresh entry panel");
+    }
+  }
+  async fn handle_message(&self, ctx: Context, msg: Message) {
+    if msg.channel_id != self.config.channel_id || msg.author.id == self.config.bot_id {
       return;
     }
-
-    let bot = match config::get("BOT_ID") {
-      Ok(id) => id,
-      Err(e) => {
-        tracing::warn!(error = %e, "Failed to get BOT_ID from environment variables");
-        return;
-      }
+    // 停止時は予約済みの再投稿を終えてから止める。停止処理中は予約しない
+    // (予約してから受け付けを断ると、予約が消化されずに残り以降の再投稿がすべてまとめられてしまう)
+    let Some(in_flight) = self.shutdown.enter() else {
+      return;
     };
-    if msg.author.id.to_string() != bot {
-      let mut redis_client = self.redis_client.lock().await;
-      match panels::entry(&ctx.http, &mut redis_client).await {
-        Ok(_) => {
-          tracing::info!("Entry panel update successfully");
-        }
-        Err(e) => {
-          tracing::warn!(error = %e, "Failed to update entry panel");
-        }
-      }
-      drop(redis_client);
+    if !self.entry_debounce.schedule().await {
+      return;
     }
+    let handler = self.clone();
+    tokio::spawn(async move {
+      let _in_flight = in_flight;
+      let _running = handler.entry_debounce.wait().await;
+      let locale = handler.locale(msg.guild_id, None).await;
+      match panels::entry(&ctx.http, &handler.config, &handler.redis_client, locale).await {
+        Ok(true) => tracing::info!("Entry panel update successfully"),
+        Ok(false) => tracing::debug!("Entry panel is already among recent messages"),
+        Err(e) => e.log("Failed to update entry panel"),
+      }
+    }.in_current_span());
   }
-  async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+  async fn handle_interaction(&self, ctx: Context, interaction: Interaction) {
     match interaction {
-      Interaction::Component(component) => {
-        match component.data.custom_id.as_str() {
-          "募集を作成" => {
-            self.create(component.user.id).await;
-            match self.server(component.user.id, &ctx.http, &component).await {
-              Err(e) => tracing::warn!(error = %e, "Failed to create server selection interaction"),
-              _ => {}
-            }
-          }
-          "サーバー選択" => {
-            let _ = component.defer(&ctx.http).await;
-            if let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind {
-              self.set(component.user.id, |data| {
-                data.server = ApServer::from_str(&values[0]).unwrap_or(ApServer::Tokyo);
-              }).await;
-            }
-            match self.mode(&ctx.http, component.user.id).await {
-              Err(e) => tracing::warn!(error = %e, "Failed to create mode selection interaction"),
-              _ => {}
-            }
-          }
-          "モード選択" => {
-            let _ = component.defer(&ctx.http).await;
-            if let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind {
-              self.set(component.user.id, |data| {
-                data.mode = Mode::from_str(&values[0]).unwrap_or(Mode::Unrated);
-              }).await;
-              if Mode::from_str(&values[0]).unwrap_or(Mode::Unrated) == Mode::Competitive {
-                match self.rank(&ctx.http, component.user.id).await {
-                  Err(e) => tracing::warn!(error = %e, "Failed to create rank selection interaction"),
-                  _ => {}
-                }
-              } else {
-                match self.member(&ctx.http, component.user.id, &values[0]).await {
-                  Err(e) => tracing::warn!(error = %e, "Failed to create member selection interaction"),
-                  _ => {}
-                }
-              }
-            }
-          }
-          "人数選択" => {
-            if let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind {
-              self.set(component.user.id, |data| {
-                data.member = Member::from_str(&values[0]).unwrap_or(Member::FullParty);
-              }).await;
-              match self.message(&ctx.http, &component).await {
-                Err(e) => tracing::warn!(error = %e, "Failed to create message interaction"),
-                _ => {}
-              }
-            }
-          }
-          "ランク選択" => {
-            let _ = component.defer(&ctx.http).await;
-            if let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind {
-              self.set(component.user.id, |data| {
-                data.rank = Some(Rank::from_str(&values[0]).unwrap_or(Rank::Unranked));
-              }).await;
-              match self.member(&ctx.http, component.user.id, Mode::Competitive.as_str()).await {
-                Err(e) => tracing::warn!(error = %e, "Failed to create member selection interaction"),
-                _ => {}
-              }
-            }
-          }
-          "参加する" => {
-            let mut redis_client = self.get_redis_client().await;
-            match buttons::join(&mut redis_client, component.user.id, component.message.id).await {
-              Ok(buttons::JoinResponse::Joined) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集に参加しました。")
-                    .ephemeral(true)
-                )).await.map_err(|e| {
-                  tracing::warn!(error = %e, "Failed to create join response");
-                }).ok();
-                let is_fill = panels::edit(&ctx.http, &mut redis_client, component.message.id).await.map_err(|e| {
-                  tracing::warn!(error = %e, "Failed to edit panel after join");
-                });
-                if let Ok(if_fill) = is_fill {
-                  if !if_fill { return; }
-                  match redis_client.get_webhook_data(component.message.id).await {
-                    Err(e) => tracing::warn!(error = %e, "Failed to get webhook data after join"),
-                    Ok(webhook_data) => {
-                      let joined_users = webhook_data.joined.iter()
-                        .map(|&u| format!("<@{}>", u.get()))
-                        .collect::<Vec<String>>()
-                        .join(" ");
-                      component.message.reply(&ctx.http, format!("{} 募集が埋まりました！", joined_users))
-                        .await
-                        .map_err(|e| tracing::warn!(error = %e, "Failed to reply after join"))
-                        .ok();
-                    }
-                  }
-                }
-              }
-              Ok(buttons::JoinResponse::AlreadyJoined) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                      .content("すでに参加しています。")
-                      .ephemeral(true)
-                  ))
-                  .await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create join response"))
-                  .ok();
-              }
-              Ok(buttons::JoinResponse::Expired) => {
-                panels::handle_expired(&ctx.http, &component, &mut self.get_redis_client().await).await;
-              }
-              Err(e) => tracing::warn!(error = %e, "Failed to join"),
-            }
-          }
-          "参加をやめる" => {
-            let mut redis_client = self.get_redis_client().await;
-            match buttons::leave(&mut redis_client, component.user.id, component.message.id).await {
-              Ok(LeaveResponse::Left) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集参加を取り消しました。") 
-                    .ephemeral(true)
-                )).await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create leave response"))
-                  .ok();
-                panels::edit(&ctx.http, &mut redis_client, component.message.id)
-                  .await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to edit panel after leave"))
-                  .ok();
-              }
-              Ok(LeaveResponse::CreatorLeave) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集作成者は募集参加を取り消せません。\n募集を削除したい場合は「削除」ボタンを押してください。")
-                    .ephemeral(true)
-                )).await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create leave response"))
-                  .ok();
-              }
-              Ok(LeaveResponse::NotJoined) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集に参加していません。")
-                    .ephemeral(true)
-                )).await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create leave response"))
-                  .ok();
-              }
-              Ok(LeaveResponse::Expired) => {
-                panels::handle_expired(&ctx.http, &component, &mut redis_client).await;
-              }
-              Err(e) => tracing::warn!(error = %e, "Failed to leave"),
-            }
-          }
-          "削除" => {
-            let mut redis_client = self.get_redis_client().await;
-            match buttons::delete(&mut redis_client, component.user.id, component.message.id).await {
-              Ok(DeleteResponse::Deleted) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集を削除しました。")
-                    .ephemeral(true)
-                )).await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create delete response"))
-                  .ok();
-                panels::delete(&ctx.http, &mut redis_client, component.message.id)
-                  .await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to delete panel after deletion"))
-                  .ok();
-              }
-              Ok(DeleteResponse::NotCreator) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集作成者のみが削除できます。")
-                    .ephemeral(true)
-                )).await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create delete response"))
-                  .ok();
-              }
-              Ok(DeleteResponse::NotJoined) => {
-                component.create_response(&ctx.http, CreateInteractionResponse::Message(
-                  CreateInteractionResponseMessage::new()
-                    .content("募集に参加していません。")
-                    .ephemeral(true)
-                )).await
-                  .map_err(|e| tracing::warn!(error = %e, "Failed to create delete response"))
-                  .ok();
-              }
-              Ok(DeleteResponse::Expired) => {
-                panels::handle_expired(&ctx.http, &component, &mut redis_client).await;
-              }
-              Err(e) => tracing::warn!(error = %e, "Failed to delete"),
-            }
-          }
-          _ => {}
-        }
-      }
-      Interaction::Modal(component) => {
-        if let ActionRowComponent::InputText(input) = &component.data.components.first().unwrap().components.first().unwrap() {
-          let webhook_data = match self.get_question_state(component.user.id).await {
-            Ok(data) => data,
-            Err(e) => {
-              tracing::warn!(error = %e, "Failed to get question state");
-              return;
-            }
+      Interaction::Component(component) => self.handle_component(&ctx, &component).await,
+      Interaction::Command(command) => {
+        let locale = self.locale(command.guild_id, Some(&command.locale)).await;
+        let result = match command.data.name.as_str() {
+          "preset" => self.preset(&ctx.http, &command, locale).await,
+          "language" => self.language(&ctx.http, &command).await,
+          "list" => self.list(&ctx.http, &command, locale).await,
+          "lifetime" => self.lifetime_settings(&ctx.http, &command, locale).await,
+          _ => Ok(()),
+        };
+        if let Err(e) = result {
+          e.log("Failed to handle command");
+          let context = ErrorContext {
+            user: command.user.id,
+            guild: command.guild_id,
+            target: &command.data.name,
+            correlation: command.id,
           };
-          let _ = component.defer(&ctx.http).await;
-          let mut redis_client = self.get_redis_client().await;
-          match panels::send(&ctx.http, &mut redis_client, &webhook_data, input.value.as_deref()).await {
-            Err(e) => tracing::warn!(error = %e, "Failed to send webhook message"),
-            _ => {}
-          }
-          if let Some(comp) = self.get_component(component.user.id).await {
-            match comp.delete_response(&ctx.http).await {
-              Err(e) => tracing::warn!(error = %e, "Failed to delete response"),
-              _ => {}
-            }
-          }
-          match self.remove_temp_data(component.user.id).await {
-            Err(e) => tracing::warn!(error = %e, "Failed to remove question state"),
-            _ => {}
-          }
+          self.report_error(&ctx, &e, &command.token, &context, locale).await;
         }
       }
+      Interaction::Modal(modal) => self.handle_modal(&ctx, &modal).await,
       _ => {}
     }
   }
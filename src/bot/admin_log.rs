@@ -0,0 +1,97 @@
+use std::{collections::{HashMap, VecDeque}, time::{Duration, Instant}};
+
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, GuildId, Http, InteractionId, Timestamp, UserId};
+use tokio::sync::Mutex;
+
+use crate::{bot::colors::ERROR_COLOR, error::BotError, metrics::time_discord};
+
+// 同じ種類のエラーはこの間隔に1件だけ投稿し、残りは件数だけを次の投稿に載せる
+const DEDUPE_WINDOW: Duration = Duration::from_secs(5 * 60);
+// 障害時にチャンネルが埋まらないよう、1分あたりの投稿数を制限する
+const RATE_LIMIT: usize = 5;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+// Embedのフィールドの上限
+const FIELD_MAX_LEN: usize = 1024;
+
+// エラーが起きたインタラクションの情報
+pub struct ErrorContext<'a> {
+  pub user: UserId,
+  pub guild: Option<GuildId>,
+  // custom_id またはコマンド名
+  pub target: &'a str,
+  // ユーザーへの返信とログに同じ値を出し、問い合わせと照合できるようにする
+  pub correlation: InteractionId,
+}
+
+// 管理者用チャンネルへのエラーの投稿。ADMIN_LOG_CHANNEL_ID が未設定なら何もしない
+pub struct AdminLog {
+  channel: Option<ChannelId>,
+  state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+  // variantごとの最後の投稿時刻と、それ以降に投稿しなかった件数
+  variants: HashMap<&'static str, (Option<Instant>, u64)>,
+  // RATE_WINDOW 内の投稿時刻
+  posted: VecDeque<Instant>,
+}
+
+impl AdminLog {
+  pub fn new(channel: Option<ChannelId>) -> Self {
+    Self { channel, state: Mutex::new(State::default()) }
+  }
+  pub async fn report(&self, http: &Http, error: &BotError, context: &ErrorContext<'_>) {
+    let Some(channel) = self.channel else {
+      return;
+    };
+    let Some(suppressed) = self.admit(error.variant()).await else {
+      return;
+    };
+    let mut embed = CreateEmbed::new()
+      .title(error.variant())
+      .description(truncate(&error.to_string()))
+      .color(ERROR_COLOR)
+      .field("ユーザー", format!("<@{}>", context.user.get()), true)
+      .field("サーバー", context.guild.map_or("-".to_string(), |g| g.get().to_string()), true)
+      .field("custom_id / コマンド", truncate(context.target), true)
+      .field("相関ID", context.correlation.get().to_string(), true)
+      .timestamp(Timestamp::now());
+    if suppressed > 0 {
+      embed = embed.field("省略した件数", suppressed.to_string(), true);
+    }
+    let message = CreateMessage::new().embed(embed);
+    if let Err(e) = time_discord("admin_log", channel.send_message(http, message)).await {
+      tracing::warn!(error = %e, "Failed to post admin log");
+    }
+  }
+  // 投稿してよければ、前回の投稿以降に省略した件数を返す
+  async fn admit(&self, variant: &'static str) -> Option<u64> {
+    let mut state = self.state.lock().await;
+    let State { variants, posted } = &mut *state;
+    let now = Instant::now();
+    while posted.front().is_some_and(|&t| now.duration_since(t) >= RATE_WINDOW) {
+      posted.pop_front();
+    }
+    let (last, suppressed) = variants.entry(variant).or_insert((None, 0));
+    let duplicated = last.is_some_and(|t| now.duration_since(t) < DEDUPE_WINDOW);
+    if duplicated || posted.len() >= RATE_LIMIT {
+      *suppressed += 1;
+      return None;
+    }
+    let count = *suppressed;
+    *last = Some(now);
+    *suppressed = 0;
+    posted.push_back(now);
+    Some(count)
+  }
+}
+
+fn truncate(text: &str) -> String {
+  if text.chars().count() <= FIELD_MAX_LEN {
+    return text.to_string();
+  }
+  let mut truncated = text.chars().take(FIELD_MAX_LEN - 1).collect::<String>();
+  truncated.push('…');
+  truncated
+}
@@ -1,10 +1,13 @@
 mod join;
 mod leave;
 mod delete;
+mod close;
 
 pub use join::join;
 pub use leave::leave;
 pub use delete::delete;
+pub use close::close;
 pub use join::JoinResponse;
 pub use leave::LeaveResponse;
 pub use delete::DeleteResponse;
+pub use close::CloseResponse;
@@ -0,0 +1,63 @@
+use redis::Script;
+use serenity::all::{MessageId, Timestamp, UserId};
+
+use crate::{bot::types::RedisClient, error::BotError};
+
+pub enum CloseResponse {
+  NotCreator,
+  AlreadyClosed,
+  Closed,
+  Expired,
+}
+
+impl CloseResponse {
+  // メトリクスのラベルに使う
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CloseResponse::NotCreator => "not_creator",
+      CloseResponse::AlreadyClosed => "already_closed",
+      CloseResponse::Closed => "closed",
+      CloseResponse::Expired => "expired",
+    }
+  }
+}
+
+// 参加と同時に押されても締め切り後に参加者が増えないよう、Redis上で締め切りを記録する
+// ARGV[1] は操作したユーザー、ARGV[2] は締め切った時刻
+// -1: 募集が存在しない、0: 作成者ではない、1: 締め切った、2: 締め切り済み
+const CLOSE_SCRIPT: &str = r"
+local creator = redis.call('HGET', KEYS[1], 'creator')
+if not creator then
+  return -1
+end
+if creator ~= ARGV[1] then
+  return 0
+end
+if redis.call('HEXISTS', KEYS[1], 'closed_at') == 1 then
+  return 2
+end
+redis.call('HSET', KEYS[1], 'closed_at', ARGV[2])
+return 1
+";
+
+pub async fn close(redis_client: &RedisClient, close_user: UserId, message: MessageId) -> Result<CloseResponse, BotError> {
+  let mut conn = redis_client.connection.clone();
+  let result: i64 = Script::new(CLOSE_SCRIPT)
+    .key(redis_client.recruit_key(message))
+    .arg(close_user.get())
+    .arg(Timestamp::now().unix_timestamp())
+    .invoke_async(&mut conn)
+    .await?;
+  match result {
+    0 => Ok(CloseResponse::NotCreator),
+    1 => {
+      // 締め切った募集は一覧に出さず、結果だけを残す
+      redis_client.remove_from_index(message).await?;
+      let webhook_data = redis_client.get_webhook_data(message).await?;
+      redis_client.record_outcome(message, &webhook_data).await?;
+      Ok(CloseResponse::Closed)
+    }
+    2 => Ok(CloseResponse::AlreadyClosed),
+    _ => Ok(CloseResponse::Expired),
+  }
+}
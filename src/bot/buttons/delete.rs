@@ -1,4 +1,3 @@
-use redis::AsyncTypedCommands;
 use serenity::all::{MessageId, UserId};
 
 use crate::{bot::types::RedisClient, error::BotError};
@@ -10,7 +9,19 @@ pub enum DeleteResponse {
   Expired,
 }
 
-pub async fn delete(redis_client: &mut RedisClient, delete_user: UserId, message: MessageId) -> Result<DeleteResponse, BotError> {
+impl DeleteResponse {
+  // メトリクスのラベルに使う
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DeleteResponse::NotCreator => "not_creator",
+      DeleteResponse::NotJoined => "not_joined",
+      DeleteResponse::Deleted => "deleted",
+      DeleteResponse::Expired => "expired",
+    }
+  }
+}
+
+pub async fn delete(redis_client: &RedisClient, delete_user: UserId, message: MessageId) -> Result<DeleteResponse, BotError> {
   let webhook_data = match redis_client.get_webhook_data(message).await {
         Ok(data) => data,
         Err(_) => return Ok(DeleteResponse::Expired),
@@ -19,11 +30,9 @@ pub async fn delete(redis_client: &mut RedisClient, delete_user: UserId, message
     return Ok(DeleteResponse::NotCreator);
   }
   if !webhook_data.joined.contains(&delete_user) {
-    return Ok(DeleteResponse::NotJoined);
+    Ok(DeleteResponse::NotJoined)
   } else {
-    let mut conn = redis_client.connection.lock().await;
-    conn.del(message.get()).await?;
-    drop(conn);
+    redis_client.delete_webhook_data(message).await?;
     Ok(DeleteResponse::Deleted)
   }
 }
\ No newline at end of file
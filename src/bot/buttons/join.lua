@@ -0,0 +1,25 @@
+local joined = redis.call('HGET', KEYS[1], 'joined')
+if not joined then
+  return -1
+end
+if redis.call('HEXISTS', KEYS[1], 'closed_at') == 1 then
+  return -2
+end
+for user in string.gmatch(joined, '[^,]+') do
+  if user == ARGV[1] then
+    return 0
+  end
+end
+if joined ~= '' then
+  joined = joined .. ','
+end
+joined = joined .. ARGV[1]
+redis.call('HSET', KEYS[1], 'joined', joined)
+local count = 0
+for _ in string.gmatch(joined, '[^,]+') do
+  count = count + 1
+end
+if count >= tonumber(ARGV[2]) then
+  redis.call('SREM', KEYS[2], ARGV[3])
+end
+return 1
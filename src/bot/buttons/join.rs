@@ -1,30 +1,53 @@
-use redis::AsyncTypedCommands;
+use std::str::FromStr;
+
+use redis::{AsyncTypedCommands, Script};
 use serenity::all::{MessageId, UserId};
-use crate::{bot::types::RedisClient, error::BotError};
+use crate::{bot::types::{Member, RedisClient}, error::BotError};
 
 pub enum JoinResponse {
     AlreadyJoined,
     Joined,
+    Closed,
     Expired,
 }
 
-pub async fn join(redis_client: &mut RedisClient, join_user: UserId, message: MessageId) -> Result<JoinResponse, BotError> {
-  let webhook_data = match redis_client.get_webhook_data(message).await {
-        Ok(data) => data,
-        Err(_) => return Ok(JoinResponse::Expired),
+impl JoinResponse {
+  // メトリクスのラベルに使う
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      JoinResponse::AlreadyJoined => "already_joined",
+      JoinResponse::Joined => "joined",
+      JoinResponse::Closed => "closed",
+      JoinResponse::Expired => "expired",
+    }
+  }
+}
+
+// 同時に押された参加ボタンで参加者が消えないよう、読み出しと書き込みをRedis上でまとめて行う
+// 満員になったら空きのある募集の索引 (KEYS[2]) から取り除く。ARGV[2] は定員、ARGV[3] はメッセージID
+// -2: 締め切り済み、-1: 募集が存在しない、0: 参加済み、1: 参加した
+// examples/join_throughput.rs からも使う
+const JOIN_SCRIPT: &str = include_str!("join.lua");
+
+pub async fn join(redis_client: &RedisClient, join_user: UserId, message: MessageId) -> Result<JoinResponse, BotError> {
+  let mut conn = redis_client.connection.clone();
+  // 定員は作成後に変わらないため、スクリプトの前に読み出してよい
+  let member = conn.hget(redis_client.recruit_key(message), "member").await?;
+  let Some(member) = member.and_then(|m| Member::from_str(&m).ok()) else {
+    return Ok(JoinResponse::Expired);
   };
-  if webhook_data.joined.contains(&join_user) {
-    Ok(JoinResponse::AlreadyJoined)
-  } else {
-    let mut joined = webhook_data.joined.clone();
-    joined.push(join_user);
-    let joined_string = joined.iter()
-      .map(|u| format!("{}", u.get()))
-      .collect::<Vec<String>>()
-      .join(",");
-    let mut conn = redis_client.connection.lock().await;
-    conn.hset(message.get(), "joined", joined_string).await?;
-    drop(conn);
-    Ok(JoinResponse::Joined)
+  let result: i64 = Script::new(JOIN_SCRIPT)
+    .key(redis_client.recruit_key(message))
+    .key(redis_client.has_slots_key())
+    .arg(join_user.get())
+    .arg(u8::from(member))
+    .arg(message.get())
+    .invoke_async(&mut conn)
+    .await?;
+  match result {
+    -2 => Ok(JoinResponse::Closed),
+    0 => Ok(JoinResponse::AlreadyJoined),
+    1 => Ok(JoinResponse::Joined),
+    _ => Ok(JoinResponse::Expired),
   }
 }
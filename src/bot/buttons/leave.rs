@@ -1,4 +1,4 @@
-use redis::AsyncTypedCommands;
+use redis::Script;
 use serenity::all::{MessageId, UserId};
 
 use crate::{bot::types::RedisClient, error::BotError};
@@ -7,30 +7,78 @@ pub enum LeaveResponse {
   CreatorLeave,
   NotJoined,
   Left,
+  Closed,
   Expired,
 }
 
-pub async fn leave(redis_client: &mut RedisClient, leave_user: UserId, message: MessageId) -> Result<LeaveResponse, BotError> {
+impl LeaveResponse {
+  // メトリクスのラベルに使う
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LeaveResponse::CreatorLeave => "creator_leave",
+      LeaveResponse::NotJoined => "not_joined",
+      LeaveResponse::Left => "left",
+      LeaveResponse::Closed => "closed",
+      LeaveResponse::Expired => "expired",
+    }
+  }
+}
+
+// 参加と同時に処理されても他の参加者が消えないよう、Redis上で参加者を取り除く
+// 空きができたら空きのある募集の索引 (KEYS[2]) に追加する。ARGV[2] は定員、ARGV[3] はメッセージID
+// -2: 締め切り済み、-1: 募集が存在しない、0: 参加していない、1: 取り除いた
+const LEAVE_SCRIPT: &str = r"
+local joined = redis.call('HGET', KEYS[1], 'joined')
+if not joined then
+  return -1
+end
+if redis.call('HEXISTS', KEYS[1], 'closed_at') == 1 then
+  return -2
+end
+local rest = {}
+local found = false
+for user in string.gmatch(joined, '[^,]+') do
+  if user == ARGV[1] then
+    found = true
+  else
+    table.insert(rest, user)
+  end
+end
+if not found then
+  return 0
+end
+redis.call('HSET', KEYS[1], 'joined', table.concat(rest, ','))
+if #rest < tonumber(ARGV[2]) then
+  redis.call('SADD', KEYS[2], ARGV[3])
+end
+return 1
+";
+
+pub async fn leave(redis_client: &RedisClient, leave_user: UserId, message: MessageId) -> Result<LeaveResponse, BotError> {
   let webhook_data = match redis_client.get_webhook_data(message).await {
         Ok(data) => data,
         Err(_) => return Ok(LeaveResponse::Expired),
   };
+  // 締め切った募集では作成者を含めて誰も参加をやめられない
+  if webhook_data.closed_at.is_some() {
+    return Ok(LeaveResponse::Closed);
+  }
   if webhook_data.creator == leave_user {
     return Ok(LeaveResponse::CreatorLeave);
   }
-  if !webhook_data.joined.contains(&leave_user) {
-    Ok(LeaveResponse::NotJoined)
-  } else {
-    let mut joined = webhook_data.joined.clone();
-    joined.retain(|&u| u != leave_user);
-    let joined_string = joined
-      .iter()
-      .map(|&u| format!("{}", u.get()))
-      .collect::<Vec<String>>()
-      .join(",");
-    let mut conn = redis_client.connection.lock().await;
-    conn.hset(message.get(), "joined", joined_string).await?;
-    drop(conn);
-    Ok(LeaveResponse::Left)
+  let mut conn = redis_client.connection.clone();
+  let result: i64 = Script::new(LEAVE_SCRIPT)
+    .key(redis_client.recruit_key(message))
+    .key(redis_client.has_slots_key())
+    .arg(leave_user.get())
+    .arg(u8::from(webhook_data.member))
+    .arg(message.get())
+    .invoke_async(&mut conn)
+    .await?;
+  match result {
+    -2 => Ok(LeaveResponse::Closed),
+    0 => Ok(LeaveResponse::NotJoined),
+    1 => Ok(LeaveResponse::Left),
+    _ => Ok(LeaveResponse::Expired),
   }
-}
\ No newline at end of file
+}
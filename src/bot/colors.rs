@@ -9,3 +9,5 @@ pub const GOLD_COLOR: u32 = 0xeabd44;
 pub const SILVER_COLOR: u32 = 0xd9e0dd;
 pub const BRONZE_COLOR: u32 = 0x865900;
 pub const IRON_COLOR: u32 = 0x4f4f4f;
+pub const ERROR_COLOR: u32 = 0xe74c3c;
+pub const CLOSED_COLOR: u32 = 0x99aab5;
@@ -0,0 +1,49 @@
+mod language;
+mod lifetime;
+pub mod list;
+mod preset;
+
+use serenity::all::{CacheHttp, Command, CommandInteraction, CommandOptionType, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage, Http};
+
+use crate::{bot::{locale::Locale, types::WebhookDataExt}, error::BotError};
+
+// コマンドの説明文は日本語を既定にして英語ロケールの翻訳を付ける
+const ENGLISH_LOCALES: [&str; 2] = ["en-US", "en-GB"];
+
+pub async fn register<T>(http: T) -> Result<(), BotError>
+where
+  T: AsRef<Http> + CacheHttp + Copy,
+{
+  Command::set_global_commands(http, vec![preset::command(), language::command(), list::command(), lifetime::command()]).await?;
+  Ok(())
+}
+
+fn option(kind: CommandOptionType, name: &str, ja: &str, en: &str) -> CreateCommandOption {
+  ENGLISH_LOCALES.into_iter().fold(
+    CreateCommandOption::new(kind, name, ja),
+    |option, locale| option.description_localized(locale, en),
+  )
+}
+
+fn choices<E: WebhookDataExt>(name: &str, ja: &str, en: &str) -> CreateCommandOption {
+  E::variants().fold(
+    option(CommandOptionType::String, name, ja, en),
+    |option, v| option.add_string_choice_localized(
+      v.label(Locale::Ja),
+      v.as_str(),
+      ENGLISH_LOCALES.map(|locale| (locale, v.label(Locale::En))),
+    ),
+  )
+}
+
+async fn reply<T>(http: T, command: &CommandInteraction, content: &str) -> Result<(), BotError>
+where
+  T: AsRef<Http> + CacheHttp + Copy,
+{
+  command.create_response(http, CreateInteractionResponse::Message(
+    CreateInteractionResponseMessage::new()
+      .content(content)
+      .ephemeral(true)
+  )).await?;
+  Ok(())
+}
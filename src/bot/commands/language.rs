@@ -0,0 +1,52 @@
+use serenity::all::{CacheHttp, CommandInteraction, CommandOptionType, CreateCommand, Http, Permissions, ResolvedValue};
+use std::str::FromStr;
+
+use crate::{
+  bot::{
+    commands::{option, reply, ENGLISH_LOCALES},
+    locale::{Locale, Text},
+    Handler,
+  },
+  error::BotError,
+};
+
+pub fn command() -> CreateCommand {
+  let language = option(CommandOptionType::String, "language", "表示言語", "Display language")
+    .required(true)
+    .add_string_choice(Locale::Ja.name(), Locale::Ja.as_str())
+    .add_string_choice(Locale::En.name(), Locale::En.as_str())
+    .add_string_choice_localized("ユーザー設定に合わせる", "auto", ENGLISH_LOCALES.map(|locale| (locale, "Follow user setting")));
+  ENGLISH_LOCALES.into_iter()
+    .fold(
+      CreateCommand::new("language").description("このサーバーでのBotの表示言語を設定します"),
+      |command, locale| command.description_localized(locale, "Set the bot's display language for this server"),
+    )
+    .default_member_permissions(Permissions::MANAGE_GUILD)
+    .dm_permission(false)
+    .add_option(language)
+}
+
+impl Handler {
+  pub async fn language<T>(&self, http: T, command: &CommandInteraction) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let Some(guild) = command.guild_id else {
+      return Ok(());
+    };
+    let locale = command.data.options().into_iter()
+      .find_map(|o| match o.value {
+        ResolvedValue::String(s) if o.name == "language" => Locale::from_str(s).ok(),
+        _ => None,
+      });
+    self.redis_client.set_guild_locale(guild, locale).await?;
+    // 設定後の言語で応答する
+    match locale {
+      Some(locale) => reply(http, command, &Text::LanguageSet.format(locale, &[locale.name()])).await,
+      None => {
+        let locale = Locale::from_discord(&command.locale);
+        reply(http, command, Text::LanguageReset.get(locale)).await
+      }
+    }
+  }
+}
@@ -0,0 +1,62 @@
+use serenity::all::{CacheHttp, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, Http, Permissions, ResolvedValue};
+use std::str::FromStr;
+
+use crate::{
+  bot::{
+    commands::{option, reply, ENGLISH_LOCALES},
+    locale::{Locale, Text},
+    types::{Lifetime, WebhookDataExt},
+    Handler,
+  },
+  error::BotError,
+};
+
+pub fn command() -> CreateCommand {
+  // 開始時刻までは募集ごとに時刻を入力するため、既定値や上限にはできない
+  let lifetimes = |name: &str, ja: &str, en: &str| Lifetime::variants()
+    .filter(|lifetime| lifetime.seconds().is_some())
+    .fold(
+      option(CommandOptionType::String, name, ja, en),
+      |option: CreateCommandOption, l| option.add_string_choice_localized(
+        l.label(Locale::Ja),
+        l.as_str(),
+        ENGLISH_LOCALES.map(|locale| (locale, l.label(Locale::En))),
+      ),
+    );
+  ENGLISH_LOCALES.into_iter()
+    .fold(
+      CreateCommand::new("lifetime").description("このサーバーの募集の期限を設定します"),
+      |command, locale| command.description_localized(locale, "Set how long recruitments stay open in this server"),
+    )
+    .default_member_permissions(Permissions::MANAGE_GUILD)
+    .dm_permission(false)
+    .add_option(lifetimes("default", "作成フローで最初に選択されている期限", "Lifetime selected by default"))
+    .add_option(lifetimes("max", "選択できる期限の上限", "Maximum lifetime"))
+}
+
+impl Handler {
+  pub async fn lifetime_settings<T>(&self, http: T, command: &CommandInteraction, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let Some(guild) = command.guild_id else {
+      return Ok(());
+    };
+    let get = |key: &str| command.data.options().into_iter().find_map(|o| match o.value {
+      ResolvedValue::String(s) if o.name == key => Lifetime::from_str(s).ok(),
+      _ => None,
+    });
+    let (default, max) = (get("default"), get("max"));
+    let current = self.redis_client.get_lifetime_settings(guild).await?;
+    let new_default = default.unwrap_or(current.default);
+    if let (Some(default), Some(max)) = (new_default.seconds(), max.or(current.max).and_then(Lifetime::seconds))
+      && default > max
+    {
+      return reply(http, command, Text::LifetimeDefaultTooLong.get(locale)).await;
+    }
+    self.redis_client.set_lifetime_settings(guild, default, max).await?;
+    let settings = self.redis_client.get_lifetime_settings(guild).await?;
+    let max = settings.max.map_or(Text::NoLimit.get(locale), |l| l.label(locale));
+    reply(http, command, &Text::LifetimeSet.format(locale, &[settings.default.label(locale), max])).await
+  }
+}
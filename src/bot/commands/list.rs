@@ -0,0 +1,122 @@
+use serenity::all::{ButtonStyle, CacheHttp, CommandInteraction, CommandOptionType, CreateActionRow, CreateButton, CreateCommand, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, Http, ResolvedValue};
+use std::str::FromStr;
+
+use crate::{
+  bot::{
+    colors::BASE_COLOR,
+    commands::{choices, option, ENGLISH_LOCALES},
+    custom_id::CustomId,
+    locale::{Locale, Text},
+    types::{ApServer, Mode, Rank, RecruitmentFilter, RedisClient},
+    Handler,
+  },
+  config::Config,
+  error::BotError,
+};
+
+// 1ページに表示する募集の数。参加ボタンを1行に並べるため5以下にする
+const PAGE_SIZE: usize = 5;
+
+pub fn command() -> CreateCommand {
+  ENGLISH_LOCALES.into_iter()
+    .fold(
+      CreateCommand::new("list").description("募集中の募集を表示します"),
+      |command, locale| command.description_localized(locale, "Show open recruitments"),
+    )
+    .dm_permission(false)
+    .add_option(choices::<ApServer>("server", "サーバー", "Server"))
+    .add_option(choices::<Mode>("mode", "モード", "Mode"))
+    .add_option(choices::<Rank>("rank", "ランク", "Rank"))
+    .add_option(option(CommandOptionType::Boolean, "has_slots", "空きのある募集だけを表示する", "Only show recruitments with open slots"))
+}
+
+impl Handler {
+  pub async fn list<T>(&self, http: T, command: &CommandInteraction, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let options = command.data.options();
+    let get = |key: &str| options.iter().find_map(|o| match o.value {
+      ResolvedValue::String(s) if o.name == key => Some(s),
+      _ => None,
+    });
+    let filter = RecruitmentFilter {
+      server: get("server").and_then(|s| ApServer::from_str(s).ok()),
+      mode: get("mode").and_then(|m| Mode::from_str(m).ok()),
+      rank: get("rank").and_then(|r| Rank::from_str(r).ok()),
+      has_slots: options.iter().any(|o| o.name == "has_slots" && matches!(o.value, ResolvedValue::Boolean(true))),
+    };
+    let (embed, components) = page(&self.config, &self.redis_client, &filter, 0, locale).await?;
+    let response = CreateInteractionResponse::Message(
+      CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(components)
+        .ephemeral(true)
+    );
+    command.create_response(http, response).await?;
+    Ok(())
+  }
+}
+
+// 一覧の page ページ目 (0始まり)。範囲外の場合は最後のページを表示する
+pub async fn page(config: &Config, redis_client: &RedisClient, filter: &RecruitmentFilter, page: usize, locale: Locale) -> Result<(CreateEmbed, Vec<CreateActionRow>), BotError> {
+  // 表示するページの募集だけを読み出す。索引に残っていても期限切れ等で読み出せなかった募集は
+  // get_recruitments が索引から取り除くため、その場合は索引を読み直してページ数を数え直す
+  let (page, pages, shown) = loop {
+    let ids = redis_client.find_recruitments(filter).await?;
+    if ids.is_empty() {
+      let embed = CreateEmbed::new()
+        .description(Text::NoMatchingRecruitments.get(locale))
+        .color(BASE_COLOR);
+      return Ok((embed, Vec::new()));
+    }
+    let pages = ids.len().div_ceil(PAGE_SIZE);
+    let page = page.min(pages - 1);
+    let start = page * PAGE_SIZE;
+    let page_ids = &ids[start..(start + PAGE_SIZE).min(ids.len())];
+    let shown = redis_client.get_recruitments(page_ids).await?;
+    if shown.len() == page_ids.len() {
+      break (page, pages, shown);
+    }
+  };
+  let mut lines = Vec::new();
+  let mut join_buttons = Vec::new();
+  for (i, (id, data)) in shown.iter().enumerate() {
+    let number = (i + 1).to_string();
+    lines.push(format!(
+      "{}. [{}]({}) ・ {}",
+      number,
+      data.summary(locale),
+      id.link(config.channel_id, Some(redis_client.guild)),
+      Text::SlotsLeft.format(locale, &[&data.slots_left().to_string()]),
+    ));
+    join_buttons.push(
+      CreateButton::new(CustomId::ListJoin.with_payload(id.get()))
+        .label(format!("{}. {}", number, Text::JoinButton.get(locale)))
+        .style(ButtonStyle::Secondary)
+        .disabled(data.slots_left() == 0)
+    );
+  }
+  let embed = CreateEmbed::new()
+    .title(Text::ListTitle.format(locale, &[&(page + 1).to_string(), &pages.to_string()]))
+    .description(lines.join("\n"))
+    .color(BASE_COLOR);
+  let pager = |label: Text, target: usize, disabled: bool| {
+    CreateButton::new(CustomId::ListPage.with_payload(format!("{}:{}", target, filter)))
+      .label(label.get(locale))
+      .style(ButtonStyle::Secondary)
+      .disabled(disabled)
+  };
+  // 前後のボタンのcustom_idが重複しないよう、無効なボタンも別のページを指す
+  let pagers = vec![
+    pager(Text::PreviousPage, page.saturating_sub(1), page == 0),
+    pager(Text::NextPage, page + 1, page + 1 >= pages),
+  ];
+  // 空のボタンの行はDiscordに拒否される
+  let mut components = Vec::new();
+  if !join_buttons.is_empty() {
+    components.push(CreateActionRow::Buttons(join_buttons));
+  }
+  components.push(CreateActionRow::Buttons(pagers));
+  Ok((embed, components))
+}
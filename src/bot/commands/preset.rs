@@ -0,0 +1,147 @@
+use serenity::all::{CacheHttp, CommandInteraction, CommandOptionType, CreateCommand, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, Http, ResolvedOption, ResolvedValue};
+use std::str::FromStr;
+
+use crate::{
+  bot::{
+    colors::BASE_COLOR,
+    commands::{choices, option, reply, ENGLISH_LOCALES},
+    custom_id::SessionId,
+    locale::{Locale, Text},
+    questions::message_modal,
+    types::{ApServer, Lifetime, Member, Mode, Preset, Rank, WebhookData, WebhookDataExt},
+    Handler,
+  },
+  error::BotError,
+};
+
+// 1ユーザーが保存できるプリセットの数 (作成フローのセレクトメニューの上限)
+const MAX_PRESETS: usize = 25;
+
+pub fn command() -> CreateCommand {
+  let name = || option(CommandOptionType::String, "name", "プリセット名", "Preset name")
+    .required(true)
+    .max_length(50);
+  let save = option(CommandOptionType::SubCommand, "save", "募集のプリセットを保存します", "Save a recruitment preset")
+    .add_sub_option(name())
+    .add_sub_option(choices::<ApServer>("server", "サーバー", "Server").required(true))
+    .add_sub_option(choices::<Mode>("mode", "モード", "Mode").required(true))
+    .add_sub_option(choices::<Member>("member", "人数", "Party size").required(true))
+    .add_sub_option(choices::<Rank>("rank", "ランク (コンペティティブのみ)", "Rank (competitive only)"))
+    .add_sub_option(choices::<Lifetime>("lifetime", "募集の期限 (省略時はサーバーの既定値)", "Recruitment lifetime (server default if omitted)"))
+    .add_sub_option(
+      option(CommandOptionType::String, "message", "募集メッセージ", "Recruitment message")
+        .max_length(100)
+    );
+  let list = option(CommandOptionType::SubCommand, "list", "保存したプリセットを表示します", "Show your saved presets");
+  let delete = option(CommandOptionType::SubCommand, "delete", "プリセットを削除します", "Delete a preset")
+    .add_sub_option(name());
+  let use_ = option(CommandOptionType::SubCommand, "use", "プリセットから募集を作成します", "Create a recruitment from a preset")
+    .add_sub_option(name());
+  ENGLISH_LOCALES.into_iter()
+    .fold(
+      CreateCommand::new("preset").description("募集のプリセットを管理します"),
+      |command, locale| command.description_localized(locale, "Manage recruitment presets"),
+    )
+    .add_option(save)
+    .add_option(list)
+    .add_option(delete)
+    .add_option(use_)
+}
+
+impl Handler {
+  pub async fn preset<T>(&self, http: T, command: &CommandInteraction, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let Some(ResolvedOption { name, value: ResolvedValue::SubCommand(options), .. }) = command.data.options().into_iter().next() else {
+      return Ok(());
+    };
+    let get = |key: &str| options.iter().find_map(|o| match o.value {
+      ResolvedValue::String(s) if o.name == key => Some(s),
+      _ => None,
+    });
+    let redis_client = &self.redis_client;
+    let user = command.user.id;
+    match name {
+      "save" => {
+        let preset_name = get("name").unwrap_or_default();
+        let server = get("server").and_then(|s| ApServer::from_str(s).ok()).unwrap_or(ApServer::Tokyo);
+        let mode = get("mode").and_then(|m| Mode::from_str(m).ok()).unwrap_or(Mode::Unrated);
+        let member = get("member").and_then(|m| Member::from_str(m).ok()).unwrap_or(Member::FullParty);
+        if !member.is_available(mode) {
+          return reply(http, command, &Text::PresetUnavailableMember.format(locale, &[mode.label(locale), member.label(locale)])).await;
+        }
+        let rank = match mode {
+          Mode::Competitive => Some(get("rank").and_then(|r| Rank::from_str(r).ok()).unwrap_or(Rank::Unranked)),
+          _ => None,
+        };
+        let names = redis_client.get_preset_names(user).await?;
+        if names.len() >= MAX_PRESETS && !names.iter().any(|n| n == preset_name) {
+          return reply(http, command, &Text::PresetLimit.format(locale, &[&MAX_PRESETS.to_string()])).await;
+        }
+        let preset = Preset {
+          name: preset_name.to_string(),
+          data: WebhookData {
+            server,
+            mode,
+            rank,
+            member,
+            lifetime: get("lifetime").and_then(|l| Lifetime::from_str(l).ok()),
+            ..WebhookData::new(user)
+          },
+          message: get("message").map(str::to_string),
+        };
+        redis_client.store_preset(user, &preset).await?;
+        reply(http, command, &Text::PresetSaved.format(locale, &[&preset.name, &preset.data.summary(locale)])).await
+      }
+      "list" => {
+        let names = redis_client.get_preset_names(user).await?;
+        if names.is_empty() {
+          return reply(http, command, Text::PresetEmpty.get(locale)).await;
+        }
+        let mut embed = CreateEmbed::new()
+          .title(Text::PresetListTitle.get(locale))
+          .color(BASE_COLOR);
+        for name in names {
+          if let Some(preset) = redis_client.get_preset(user, &name).await? {
+            let value = match preset.message {
+              Some(message) => format!("{}\n{}", preset.data.summary(locale), message),
+              None => preset.data.summary(locale),
+            };
+            embed = embed.field(name, value, false);
+          }
+        }
+        let response = CreateInteractionResponse::Message(
+          CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .ephemeral(true)
+        );
+        command.create_response(http, response).await?;
+        Ok(())
+      }
+      "delete" => {
+        let preset_name = get("name").unwrap_or_default();
+        if redis_client.delete_preset(user, preset_name).await? {
+          reply(http, command, &Text::PresetDeleted.format(locale, &[preset_name])).await
+        } else {
+          reply(http, command, &Text::PresetNotFound.format(locale, &[preset_name])).await
+        }
+      }
+      "use" => {
+        let preset_name = get("name").unwrap_or_default();
+        let Some(mut preset) = redis_client.get_preset(user, preset_name).await? else {
+          return reply(http, command, &Text::PresetNotFound.format(locale, &[preset_name])).await;
+        };
+        self.clamp_lifetime(&mut preset.data).await?;
+        let start = preset.data.lifetime == Some(Lifetime::UntilStart);
+        // コマンドのインタラクションIDをセッションにする
+        let session = SessionId(command.id.get());
+        self.start_session(session, preset.data).await;
+        let response = CreateInteractionResponse::Modal(message_modal(session, preset.message.as_deref(), start, locale));
+        command.create_response(http, response).await?;
+        Ok(())
+      }
+      _ => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,156 @@
+mod close;
+mod delete;
+mod join;
+mod leave;
+mod list;
+mod questions;
+
+use serenity::{
+  all::{CacheHttp, ComponentInteraction, Context, CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage, InteractionId, ModalInteraction, UserId},
+  async_trait,
+  builder::Builder,
+};
+
+use crate::{bot::{admin_log::ErrorContext, custom_id::{self, CustomId, SessionId}, locale::{Locale, Text}, Handler}, error::BotError};
+
+// ボタン、セレクトメニュー、モーダルごとの処理
+#[async_trait]
+pub trait ComponentHandler: Sync {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError>;
+}
+
+// custom_idと処理の対応。ボタンやメニューを追加するときはここに登録する
+fn component_handler(id: CustomId) -> Option<&'static dyn ComponentHandler> {
+  match id {
+    CustomId::Create => Some(&questions::Create),
+    CustomId::SelectServer => Some(&questions::SelectServer),
+    CustomId::SelectMode => Some(&questions::SelectMode),
+    CustomId::SelectRank => Some(&questions::SelectRank),
+    CustomId::SelectMember => Some(&questions::SelectMember),
+    CustomId::SelectLifetime => Some(&questions::SelectLifetime),
+    CustomId::SelectPreset => Some(&questions::SelectPreset),
+    CustomId::BackToServer => Some(&questions::BackToServer),
+    CustomId::BackToMode => Some(&questions::BackToMode),
+    CustomId::BackToRank => Some(&questions::BackToRank),
+    CustomId::BackToMember => Some(&questions::BackToMember),
+    CustomId::LastSettings => Some(&questions::LastSettings),
+    CustomId::Cancel => Some(&questions::Cancel),
+    CustomId::Join => Some(&join::Join),
+    CustomId::Leave => Some(&leave::Leave),
+    CustomId::Delete => Some(&delete::Delete),
+    CustomId::Close => Some(&close::Close),
+    CustomId::ListPage => Some(&list::ListPage),
+    CustomId::ListJoin => Some(&list::ListJoin),
+    // モーダルとその入力欄はhandle_modalで処理する
+    CustomId::MessageModal | CustomId::MessageInput | CustomId::StartInput => None,
+  }
+}
+
+impl Handler {
+  pub async fn handle_component(&self, ctx: &Context, component: &ComponentInteraction) {
+    let Some((id, handler, session)) = custom_id::parse(&component.data.custom_id)
+      .and_then(|(id, session)| Some((id, component_handler(id)?, session)))
+    else {
+      // 応答しないと「インタラクションに失敗しました」と表示されるため、何もせず受け付ける
+      tracing::debug!("Unknown component");
+      if let Err(e) = component.defer(&ctx.http).await {
+        tracing::warn!(error = %e, "Failed to acknowledge unknown component");
+      }
+      return;
+    };
+    let locale = self.locale(component.guild_id, Some(&component.locale)).await;
+    // 終了したフローや、セッション導入前に表示したフローのコンポーネント
+    if id.is_flow() && !self.is_session_of(session, component.user.id).await {
+      if let Err(e) = reply_ephemeral(&ctx.http, component, Text::SessionExpired.get(locale)).await {
+        e.log("Failed to reply to stale session");
+      }
+      return;
+    }
+    if let (true, Some(session)) = (id.is_flow(), session) {
+      self.keep_component(session, component).await;
+    }
+    if let Err(e) = handler.handle(self, ctx, component, locale).await {
+      e.log("Failed to handle component interaction");
+      let context = ErrorContext {
+        user: component.user.id,
+        guild: component.guild_id,
+        target: &component.data.custom_id,
+        correlation: component.id,
+      };
+      self.report_error(ctx, &e, &component.token, &context, locale).await;
+    }
+  }
+  pub async fn handle_modal(&self, ctx: &Context, modal: &ModalInteraction) {
+    let result = match custom_id::parse(&modal.data.custom_id) {
+      Some((CustomId::MessageModal, session)) => questions::submit_message(self, ctx, modal, session).await,
+      _ => {
+        tracing::debug!("Unknown modal");
+        if let Err(e) = modal.defer(&ctx.http).await {
+          tracing::warn!(error = %e, "Failed to acknowledge unknown modal");
+        }
+        return;
+      }
+    };
+    if let Err(e) = result {
+      e.log("Failed to handle modal interaction");
+      let locale = self.locale(modal.guild_id, Some(&modal.locale)).await;
+      let context = ErrorContext {
+        user: modal.user.id,
+        guild: modal.guild_id,
+        target: &modal.data.custom_id,
+        correlation: modal.id,
+      };
+      self.report_error(ctx, &e, &modal.token, &context, locale).await;
+    }
+  }
+}
+
+impl Handler {
+  // 失敗したことを操作したユーザーに伝え、管理者用チャンネルにも投稿する
+  pub async fn report_error(&self, ctx: &Context, error: &BotError, token: &str, context: &ErrorContext<'_>, locale: Locale) {
+    reply_error(ctx, context.correlation, token, error, locale).await;
+    self.admin_log.report(&ctx.http, error, context).await;
+  }
+  async fn is_session_of(&self, session: Option<SessionId>, user: UserId) -> bool {
+    match session {
+      Some(session) => self.is_active_session(session, user).await,
+      None => false,
+    }
+  }
+}
+
+// ルーターで検証済みのセッションを取り出す
+pub fn session(component: &ComponentInteraction) -> Result<SessionId, BotError> {
+  custom_id::parse(&component.data.custom_id)
+    .and_then(|(_, session)| session)
+    .ok_or(BotError::SessionNotFound)
+}
+
+// 操作したユーザーにだけ見えるメッセージで返信する
+pub async fn reply_ephemeral<T: CacheHttp>(http: T, component: &ComponentInteraction, content: impl Into<String>) -> Result<(), BotError> {
+  component.create_response(http, CreateInteractionResponse::Message(
+    CreateInteractionResponseMessage::new()
+      .content(content)
+      .ephemeral(true)
+  )).await?;
+  Ok(())
+}
+
+// まだ応答していなければ応答し、応答・defer済みで応答できなければフォローアップで送る
+async fn reply_error(ctx: &Context, id: InteractionId, token: &str, error: &BotError, locale: Locale) {
+  let content = error.user_message(locale, id);
+  let response = CreateInteractionResponse::Message(
+    CreateInteractionResponseMessage::new()
+      .content(&content)
+      .ephemeral(true)
+  );
+  if response.execute(ctx, (id, token)).await.is_ok() {
+    return;
+  }
+  let followup = CreateInteractionResponseFollowup::new()
+    .content(content)
+    .ephemeral(true);
+  if let Err(e) = followup.execute(ctx, (None, token)).await {
+    tracing::warn!(error = %e, "Failed to reply error");
+  }
+}
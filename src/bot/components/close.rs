@@ -0,0 +1,35 @@
+use serenity::{all::{ComponentInteraction, Context}, async_trait};
+
+use crate::{
+  bot::{buttons::{self, CloseResponse}, components::{reply_ephemeral, ComponentHandler}, locale::{Locale, Text}, panels, Handler},
+  error::BotError,
+  metrics::METRICS,
+};
+
+pub struct Close;
+
+#[async_trait]
+impl ComponentHandler for Close {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let response = buttons::close(&bot.redis_client, component.user.id, component.message.id).await?;
+    METRICS.closes.inc(&[response.as_str()]);
+    match response {
+      CloseResponse::Closed => {
+        if let Err(e) = reply_ephemeral(&ctx.http, component, Text::Closed.get(locale)).await {
+          e.log("Failed to create close response");
+        }
+        let panel_locale = bot.locale(component.guild_id, None).await;
+        panels::edit(&ctx.http, &bot.config, &bot.redis_client, component.message.id, panel_locale).await?;
+        bot.refresh_entry(ctx).await;
+        Ok(())
+      }
+      CloseResponse::NotCreator => reply_ephemeral(&ctx.http, component, Text::NotCreatorClose.get(locale)).await,
+      CloseResponse::AlreadyClosed => reply_ephemeral(&ctx.http, component, Text::RecruitmentClosed.get(locale)).await,
+      CloseResponse::Expired => {
+        panels::handle_expired(&ctx.http, &bot.config, component, component.message.id, &bot.redis_client, locale).await;
+        bot.refresh_entry(ctx).await;
+        Ok(())
+      }
+    }
+  }
+}
@@ -0,0 +1,34 @@
+use serenity::{all::{ComponentInteraction, Context}, async_trait};
+
+use crate::{
+  bot::{buttons::{self, DeleteResponse}, components::{reply_ephemeral, ComponentHandler}, locale::{Locale, Text}, panels, Handler},
+  error::BotError,
+  metrics::METRICS,
+};
+
+pub struct Delete;
+
+#[async_trait]
+impl ComponentHandler for Delete {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let response = buttons::delete(&bot.redis_client, component.user.id, component.message.id).await?;
+    METRICS.deletes.inc(&[response.as_str()]);
+    match response {
+      DeleteResponse::Deleted => {
+        if let Err(e) = reply_ephemeral(&ctx.http, component, Text::Deleted.get(locale)).await {
+          e.log("Failed to create delete response");
+        }
+        panels::delete(&ctx.http, &bot.config, &bot.redis_client, component.message.id).await?;
+        bot.refresh_entry(ctx).await;
+        Ok(())
+      }
+      DeleteResponse::NotCreator => reply_ephemeral(&ctx.http, component, Text::NotCreator.get(locale)).await,
+      DeleteResponse::NotJoined => reply_ephemeral(&ctx.http, component, Text::NotJoined.get(locale)).await,
+      DeleteResponse::Expired => {
+        panels::handle_expired(&ctx.http, &bot.config, component, component.message.id, &bot.redis_client, locale).await;
+        bot.refresh_entry(ctx).await;
+        Ok(())
+      }
+    }
+  }
+}
@@ -0,0 +1,54 @@
+use serenity::{all::{ComponentInteraction, Context, CreateMessage, MessageId}, async_trait};
+
+use crate::{
+  bot::{buttons::{self, JoinResponse}, components::{reply_ephemeral, ComponentHandler}, locale::{Locale, Text}, panels, Handler},
+  error::BotError,
+  metrics::METRICS,
+};
+
+pub struct Join;
+
+#[async_trait]
+impl ComponentHandler for Join {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    join(bot, ctx, component, component.message.id, locale).await
+  }
+}
+
+// 募集パネルと /list の参加ボタンで共通の処理。message は募集パネル
+pub async fn join(bot: &Handler, ctx: &Context, component: &ComponentInteraction, message: MessageId, locale: Locale) -> Result<(), BotError> {
+  let response = buttons::join(&bot.redis_client, component.user.id, message).await?;
+  METRICS.joins.inc(&[response.as_str()]);
+  match response {
+    JoinResponse::Joined => {
+      if let Err(e) = reply_ephemeral(&ctx.http, component, Text::Joined.get(locale)).await {
+        e.log("Failed to create join response");
+      }
+      // 募集パネルは操作したユーザーではなくサーバーの言語で表示する
+      let panel_locale = bot.locale(component.guild_id, None).await;
+      let is_fill = panels::edit(&ctx.http, &bot.config, &bot.redis_client, message, panel_locale).await?;
+      bot.refresh_entry(ctx).await;
+      if !is_fill {
+        return Ok(());
+      }
+      METRICS.fills.inc(&[]);
+      let webhook_data = bot.redis_client.get_webhook_data(message).await?;
+      let joined_users = webhook_data.joined.iter()
+        .map(|&u| format!("<@{}>", u.get()))
+        .collect::<Vec<String>>()
+        .join(" ");
+      let filled = CreateMessage::new()
+        .content(Text::Filled.format(panel_locale, &[&joined_users]))
+        .reference_message((bot.config.channel_id, message));
+      bot.config.channel_id.send_message(&ctx.http, filled).await?;
+      Ok(())
+    }
+    JoinResponse::AlreadyJoined => reply_ephemeral(&ctx.http, component, Text::AlreadyJoined.get(locale)).await,
+    JoinResponse::Closed => reply_ephemeral(&ctx.http, component, Text::RecruitmentClosed.get(locale)).await,
+    JoinResponse::Expired => {
+      panels::handle_expired(&ctx.http, &bot.config, component, message, &bot.redis_client, locale).await;
+      bot.refresh_entry(ctx).await;
+      Ok(())
+    }
+  }
+}
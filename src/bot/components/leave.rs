@@ -0,0 +1,36 @@
+use serenity::{all::{ComponentInteraction, Context}, async_trait};
+
+use crate::{
+  bot::{buttons::{self, LeaveResponse}, components::{reply_ephemeral, ComponentHandler}, locale::{Locale, Text}, panels, Handler},
+  error::BotError,
+  metrics::METRICS,
+};
+
+pub struct Leave;
+
+#[async_trait]
+impl ComponentHandler for Leave {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let response = buttons::leave(&bot.redis_client, component.user.id, component.message.id).await?;
+    METRICS.leaves.inc(&[response.as_str()]);
+    match response {
+      LeaveResponse::Left => {
+        if let Err(e) = reply_ephemeral(&ctx.http, component, Text::Left.get(locale)).await {
+          e.log("Failed to create leave response");
+        }
+        let panel_locale = bot.locale(component.guild_id, None).await;
+        panels::edit(&ctx.http, &bot.config, &bot.redis_client, component.message.id, panel_locale).await?;
+        bot.refresh_entry(ctx).await;
+        Ok(())
+      }
+      LeaveResponse::CreatorLeave => reply_ephemeral(&ctx.http, component, Text::CreatorLeave.get(locale)).await,
+      LeaveResponse::NotJoined => reply_ephemeral(&ctx.http, component, Text::NotJoined.get(locale)).await,
+      LeaveResponse::Closed => reply_ephemeral(&ctx.http, component, Text::RecruitmentClosed.get(locale)).await,
+      LeaveResponse::Expired => {
+        panels::handle_expired(&ctx.http, &bot.config, component, component.message.id, &bot.redis_client, locale).await;
+        bot.refresh_entry(ctx).await;
+        Ok(())
+      }
+    }
+  }
+}
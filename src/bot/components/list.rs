@@ -0,0 +1,44 @@
+use std::str::FromStr;
+
+use serenity::{all::{ComponentInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage, MessageId}, async_trait};
+
+use crate::{
+  bot::{commands::list, components::{join, ComponentHandler}, custom_id, locale::Locale, types::RecruitmentFilter, Handler},
+  error::BotError,
+};
+
+// /list のページ送り。custom_id は list_page:{page}:{filter}
+pub struct ListPage;
+
+#[async_trait]
+impl ComponentHandler for ListPage {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let Some((page, filter)) = custom_id::payload(&component.data.custom_id)
+      .and_then(|payload| payload.split_once(':'))
+      .and_then(|(page, filter)| Some((page.parse().ok()?, RecruitmentFilter::from_str(filter).ok()?)))
+    else {
+      return Err(BotError::ComponentInteractionNotFound);
+    };
+    let (embed, components) = list::page(&bot.config, &bot.redis_client, &filter, page, locale).await?;
+    let response = CreateInteractionResponse::UpdateMessage(
+      CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(components)
+    );
+    component.create_response(&ctx.http, response).await?;
+    Ok(())
+  }
+}
+
+// /list の参加ボタン。custom_id は list_join:{message}
+pub struct ListJoin;
+
+#[async_trait]
+impl ComponentHandler for ListJoin {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let Some(message) = custom_id::payload(&component.data.custom_id).and_then(|id| MessageId::from_str(id).ok()) else {
+      return Err(BotError::ComponentInteractionNotFound);
+    };
+    join::join(bot, ctx, component, message, locale).await
+  }
+}
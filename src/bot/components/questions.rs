@@ -0,0 +1,279 @@
+use std::str::FromStr;
+
+use chrono::Utc;
+
+use serenity::{
+  all::{ActionRowComponent, ComponentInteraction, ComponentInteractionDataKind, Context, CreateInteractionResponse, CreateInteractionResponseMessage, ModalInteraction},
+  async_trait,
+};
+
+use crate::{
+  bot::{
+    components::{session, ComponentHandler},
+    custom_id::{CustomId, SessionId},
+    locale::{Locale, Text},
+    panels,
+    questions::start_time,
+    types::{ApServer, Lifetime, Member, Mode, Rank},
+    Handler,
+  },
+  error::BotError,
+};
+
+// セレクトメニューで選ばれた値
+fn selected(component: &ComponentInteraction) -> Option<&str> {
+  match &component.data.kind {
+    ComponentInteractionDataKind::StringSelect { values } => values.first().map(String::as_str),
+    _ => None,
+  }
+}
+
+// 募集作成パネルの「募集を作成」
+pub struct Create;
+
+#[async_trait]
+impl ComponentHandler for Create {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    // 「募集を作成」のインタラクションIDをセッションにする
+    let session = SessionId(component.id.get());
+    bot.create(session, component.user.id).await;
+    bot.server(session, &ctx.http, component, locale).await
+  }
+}
+
+pub struct SelectServer;
+
+#[async_trait]
+impl ComponentHandler for SelectServer {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    if let Some(value) = selected(component) {
+      bot.set(session, |data| {
+        data.server = ApServer::from_str(value).unwrap_or(ApServer::Tokyo);
+      }).await;
+    }
+    bot.mode(&ctx.http, session, None, locale).await
+  }
+}
+
+pub struct SelectMode;
+
+#[async_trait]
+impl ComponentHandler for SelectMode {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let Some(value) = selected(component) else {
+      return Ok(());
+    };
+    let mode = Mode::from_str(value).unwrap_or(Mode::Unrated);
+    bot.set(session, |data| {
+      data.mode = mode;
+      // 戻るボタンでモードを変更した場合に備えてランクを消しておく
+      if mode != Mode::Competitive {
+        data.rank = None;
+      }
+    }).await;
+    if mode == Mode::Competitive {
+      bot.rank(&ctx.http, session, None, locale).await
+    } else {
+      bot.member(&ctx.http, session, mode, None, locale).await
+    }
+  }
+}
+
+pub struct SelectRank;
+
+#[async_trait]
+impl ComponentHandler for SelectRank {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let Some(value) = selected(component) else {
+      return Ok(());
+    };
+    bot.set(session, |data| {
+      data.rank = Some(Rank::from_str(value).unwrap_or(Rank::Unranked));
+    }).await;
+    bot.member(&ctx.http, session, Mode::Competitive, None, locale).await
+  }
+}
+
+pub struct SelectMember;
+
+#[async_trait]
+impl ComponentHandler for SelectMember {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let Some(value) = selected(component) else {
+      return Ok(());
+    };
+    bot.set(session, |data| {
+      data.member = Member::from_str(value).unwrap_or(Member::FullParty);
+    }).await;
+    let webhook_data = bot.get_question_state(session).await?;
+    bot.lifetime(&ctx.http, session, webhook_data.lifetime, locale).await
+  }
+}
+
+// 期限を選ぶとメッセージ入力のモーダルを開くため、deferしない
+pub struct SelectLifetime;
+
+#[async_trait]
+impl ComponentHandler for SelectLifetime {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let Some(value) = selected(component) else {
+      return Ok(());
+    };
+    bot.set(session, |data| {
+      data.lifetime = Lifetime::from_str(value).ok();
+    }).await;
+    bot.message(&ctx.http, component, session, None, locale).await
+  }
+}
+
+pub struct SelectPreset;
+
+#[async_trait]
+impl ComponentHandler for SelectPreset {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    match selected(component) {
+      Some(name) => bot.use_preset(&ctx.http, component, session, name, locale).await,
+      None => Ok(()),
+    }
+  }
+}
+
+pub struct LastSettings;
+
+#[async_trait]
+impl ComponentHandler for LastSettings {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    bot.last_settings(&ctx.http, component, session, locale).await
+  }
+}
+
+pub struct BackToServer;
+
+#[async_trait]
+impl ComponentHandler for BackToServer {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let webhook_data = bot.get_question_state(session).await?;
+    bot.back_to_server(&ctx.http, session, component.user.id, webhook_data.server, locale).await
+  }
+}
+
+pub struct BackToMode;
+
+#[async_trait]
+impl ComponentHandler for BackToMode {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let webhook_data = bot.get_question_state(session).await?;
+    bot.mode(&ctx.http, session, Some(webhook_data.mode), locale).await
+  }
+}
+
+pub struct BackToRank;
+
+#[async_trait]
+impl ComponentHandler for BackToRank {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let webhook_data = bot.get_question_state(session).await?;
+    bot.rank(&ctx.http, session, webhook_data.rank, locale).await
+  }
+}
+
+pub struct BackToMember;
+
+#[async_trait]
+impl ComponentHandler for BackToMember {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, locale: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    let webhook_data = bot.get_question_state(session).await?;
+    bot.member(&ctx.http, session, webhook_data.mode, Some(webhook_data.member), locale).await
+  }
+}
+
+pub struct Cancel;
+
+#[async_trait]
+impl ComponentHandler for Cancel {
+  async fn handle(&self, bot: &Handler, ctx: &Context, component: &ComponentInteraction, _: Locale) -> Result<(), BotError> {
+    let session = session(component)?;
+    let _ = component.defer(&ctx.http).await;
+    bot.cancel(&ctx.http, session).await
+  }
+}
+
+// メッセージ入力のモーダルが送信されたら募集を投稿する
+pub async fn submit_message(bot: &Handler, ctx: &Context, modal: &ModalInteraction, session: Option<SessionId>) -> Result<(), BotError> {
+  // 入力欄が見つからなければメッセージなしで投稿する
+  let message = input(modal, CustomId::MessageInput);
+  // モーダルには必ず応答しないと送信エラーになるため、終了したセッションにも返信する
+  let Some(session) = session else {
+    return reply(bot, ctx, modal, Text::SessionExpired).await;
+  };
+  if !bot.is_active_session(session, modal.user.id).await {
+    return reply(bot, ctx, modal, Text::SessionExpired).await;
+  }
+  let mut webhook_data = bot.get_question_state(session).await?;
+  // 開始時刻の誤りではフローを残し、期限を選び直して再入力できるようにする
+  let settings = bot.redis_client.get_lifetime_settings(bot.redis_client.guild).await?;
+  let now = Utc::now();
+  let expires_at = match webhook_data.lifetime.unwrap_or(settings.default).seconds() {
+    Some(seconds) => now.timestamp() + seconds.min(settings.max_seconds),
+    None => match input(modal, CustomId::StartInput).and_then(|s| start_time(s, bot.config.timezone, now)) {
+      Some(start) if start.timestamp() - now.timestamp() <= settings.max_seconds => start.timestamp(),
+      Some(_) => return reply(bot, ctx, modal, Text::StartTimeTooLate).await,
+      None => return reply(bot, ctx, modal, Text::InvalidStartTime).await,
+    },
+  };
+  webhook_data.expires_at = Some(expires_at);
+  let _ = modal.defer(&ctx.http).await;
+  let panel_locale = bot.locale(modal.guild_id, None).await;
+  // 投稿に失敗した場合はフローを残し、人数を選び直して再送信できるようにする
+  panels::send(&ctx.http, &bot.config, &bot.redis_client, &webhook_data, message, panel_locale).await?;
+  bot.refresh_entry(ctx).await;
+  // 再起動後にモーダルを開いたコンポーネントを登録した場合は、その応答ではなくモーダルから質問を削除する
+  if let Some(component) = bot.get_component(session).await
+    && let Err(e) = match component.delete_response(&ctx.http).await {
+      Err(_) => modal.delete_response(&ctx.http).await,
+      ok => ok,
+    }
+  {
+    tracing::warn!(error = %e, "Failed to delete response");
+  }
+  bot.remove_temp_data(session).await
+}
+
+// モーダルの入力欄の値
+fn input(modal: &ModalInteraction, id: CustomId) -> Option<&str> {
+  modal.data.components.iter()
+    .flat_map(|row| &row.components)
+    .find_map(|component| match component {
+      ActionRowComponent::InputText(input) if input.custom_id == id.as_str() => input.value.as_deref(),
+      _ => None,
+    })
+}
+
+async fn reply(bot: &Handler, ctx: &Context, modal: &ModalInteraction, text: Text) -> Result<(), BotError> {
+  let locale = bot.locale(modal.guild_id, Some(&modal.locale)).await;
+  modal.create_response(&ctx.http, CreateInteractionResponse::Message(
+    CreateInteractionResponseMessage::new()
+      .content(text.get(locale))
+      .ephemeral(true)
+  )).await?;
+  Ok(())
+}
@@ -0,0 +1,171 @@
+use std::{fmt::Display, str::FromStr};
+
+// コンポーネントのcustom_id。表示文言とは独立させ、as_strの値は変更しないこと
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CustomId {
+  Create,
+  SelectServer,
+  SelectMode,
+  SelectRank,
+  SelectMember,
+  SelectLifetime,
+  SelectPreset,
+  BackToServer,
+  BackToMode,
+  BackToRank,
+  BackToMember,
+  LastSettings,
+  Cancel,
+  MessageModal,
+  MessageInput,
+  StartInput,
+  Join,
+  Leave,
+  Delete,
+  Close,
+  ListPage,
+  ListJoin,
+}
+
+// 募集作成フローのセッション。同じユーザーが複数のフローを並行して進められるよう、
+// フロー内のコンポーネントのcustom_idに "{id}:{session}" の形で含める
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId(pub u64);
+
+// Discordのスノーフレークの上位ビットは 2015-01-01 からのミリ秒
+const DISCORD_EPOCH_MILLIS: i64 = 1_420_070_400_000;
+
+impl SessionId {
+  // セッションはフローを開始したインタラクションのIDのため、開始時刻 (UNIX時間) を含む
+  pub fn started_at(self) -> i64 {
+    ((self.0 >> 22) as i64 + DISCORD_EPOCH_MILLIS) / 1000
+  }
+}
+
+impl Display for SessionId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl FromStr for SessionId {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.parse().map(SessionId).map_err(|_| ())
+  }
+}
+
+// custom_idを種類とセッションに分ける。フロー外のコンポーネントの ":" 以降は payload で取り出す
+pub fn parse(custom_id: &str) -> Option<(CustomId, Option<SessionId>)> {
+  match custom_id.split_once(':') {
+    Some((id, payload)) => {
+      let id = CustomId::from_str(id).ok()?;
+      if !id.is_flow() {
+        return Some((id, None));
+      }
+      Some((id, Some(SessionId::from_str(payload).ok()?)))
+    }
+    None => Some((CustomId::from_str(custom_id).ok()?, None)),
+  }
+}
+
+// custom_idの ":" 以降
+pub fn payload(custom_id: &str) -> Option<&str> {
+  custom_id.split_once(':').map(|(_, payload)| payload)
+}
+
+impl CustomId {
+  pub fn session(self, session: SessionId) -> String {
+    self.with_payload(session)
+  }
+  // 一覧のページ等、コンポーネントに持たせる値をcustom_idに含める
+  pub fn with_payload(self, payload: impl Display) -> String {
+    format!("{}:{}", self.as_str(), payload)
+  }
+  // 募集作成フローの中で表示するコンポーネントか。セッションが必要になる
+  pub fn is_flow(self) -> bool {
+    !matches!(
+      self,
+      CustomId::Create | CustomId::Join | CustomId::Leave | CustomId::Delete | CustomId::Close | CustomId::ListPage | CustomId::ListJoin
+    )
+  }
+  pub fn as_str(self) -> &'static str {
+    match self {
+      CustomId::Create => "create",
+      CustomId::SelectServer => "select_server",
+      CustomId::SelectMode => "select_mode",
+      CustomId::SelectRank => "select_rank",
+      CustomId::SelectMember => "select_member",
+      CustomId::SelectLifetime => "select_lifetime",
+      CustomId::SelectPreset => "select_preset",
+      CustomId::BackToServer => "back_to_server",
+      CustomId::BackToMode => "back_to_mode",
+      CustomId::BackToRank => "back_to_rank",
+      CustomId::BackToMember => "back_to_member",
+      CustomId::LastSettings => "last_settings",
+      CustomId::Cancel => "cancel",
+      CustomId::MessageModal => "message_modal",
+      CustomId::MessageInput => "message_input",
+      CustomId::StartInput => "start_input",
+      CustomId::Join => "join",
+      CustomId::Leave => "leave",
+      CustomId::Delete => "delete",
+      CustomId::Close => "close",
+      CustomId::ListPage => "list_page",
+      CustomId::ListJoin => "list_join",
+    }
+  }
+  fn variants() -> impl Iterator<Item = Self> {
+    [
+      CustomId::Create,
+      CustomId::SelectServer,
+      CustomId::SelectMode,
+      CustomId::SelectRank,
+      CustomId::SelectMember,
+      CustomId::SelectLifetime,
+      CustomId::SelectPreset,
+      CustomId::BackToServer,
+      CustomId::BackToMode,
+      CustomId::BackToRank,
+      CustomId::BackToMember,
+      CustomId::LastSettings,
+      CustomId::Cancel,
+      CustomId::MessageModal,
+      CustomId::MessageInput,
+      CustomId::StartInput,
+      CustomId::Join,
+      CustomId::Leave,
+      CustomId::Delete,
+      CustomId::Close,
+      CustomId::ListPage,
+      CustomId::ListJoin,
+    ].into_iter()
+  }
+}
+
+impl FromStr for CustomId {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      // 表示文言をcustom_idにしていた頃に送信されたパネルのボタン
+      "募集を作成" => Ok(CustomId::Create),
+      "参加する" => Ok(CustomId::Join),
+      "参加をやめる" => Ok(CustomId::Leave),
+      "削除" => Ok(CustomId::Delete),
+      _ => CustomId::variants().find(|id| id.as_str() == s).ok_or(()),
+    }
+  }
+}
+
+impl Display for CustomId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+// CreateButton::new 等にそのまま渡せるようにする
+impl From<CustomId> for String {
+  fn from(id: CustomId) -> Self {
+    id.as_str().to_string()
+  }
+}
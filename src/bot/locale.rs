@@ -0,0 +1,321 @@
+use serenity::all::GuildId;
+use std::str::FromStr;
+
+use crate::bot::Handler;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+  Ja,
+  En,
+}
+
+// 表示テキストのID。文言は下の ja / en カタログで定義する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+  SelectServer,
+  SelectMode,
+  SelectRank,
+  SelectMember,
+  LastSettings,
+  LastSettingsButton,
+  PresetPlaceholder,
+  Back,
+  Cancel,
+  MessageModalTitle,
+  MessageInputLabel,
+  MessageInputPlaceholder,
+  EntryPanel,
+  CreateButton,
+  JoinButton,
+  LeaveButton,
+  DeleteButton,
+  CloseButton,
+  Participants,
+  PanelServer,
+  PanelMode,
+  PanelRank,
+  Filled,
+  Joined,
+  AlreadyJoined,
+  Left,
+  CreatorLeave,
+  NotJoined,
+  Deleted,
+  NotCreator,
+  Closed,
+  NotCreatorClose,
+  RecruitmentClosed,
+  PanelClosed,
+  Expired,
+  PresetUnavailableMember,
+  PresetLimit,
+  PresetSaved,
+  PresetEmpty,
+  PresetListTitle,
+  PresetDeleted,
+  PresetNotFound,
+  LanguageSet,
+  LanguageReset,
+  ShuttingDown,
+  SessionExpired,
+  ErrorDatabase,
+  ErrorDiscord,
+  ErrorRecruitmentNotFound,
+  ErrorPresetNotFound,
+  ErrorPanelBroken,
+  ErrorInternal,
+  ErrorCode,
+  OpenRecruitments,
+  NoOpenRecruitments,
+  SlotsLeft,
+  MoreRecruitments,
+  ListTitle,
+  NoMatchingRecruitments,
+  PreviousPage,
+  NextPage,
+  SelectLifetime,
+  StartInputLabel,
+  InvalidStartTime,
+  StartTimeTooLate,
+  PanelExpires,
+  LifetimeSet,
+  LifetimeDefaultTooLong,
+  NoLimit,
+}
+
+impl Locale {
+  // Discordのユーザーロケール (ja, en-US, ...) から変換する
+  pub fn from_discord(locale: &str) -> Self {
+    if locale.starts_with("ja") { Locale::Ja } else { Locale::En }
+  }
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Locale::Ja => "ja",
+      Locale::En => "en",
+    }
+  }
+  pub fn name(&self) -> &'static str {
+    match self {
+      Locale::Ja => "日本語",
+      Locale::En => "English",
+    }
+  }
+}
+impl FromStr for Locale {
+  type Err = &'static str;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "ja" => Ok(Locale::Ja),
+      "en" => Ok(Locale::En),
+      _ => Err("Invalid locale"),
+    }
+  }
+}
+
+impl Text {
+  pub fn get(self, locale: Locale) -> &'static str {
+    match locale {
+      Locale::Ja => ja(self),
+      Locale::En => en(self),
+    }
+  }
+  // カタログ中の {} を先頭から順に args で置き換える
+  // プリセット名等の引数に {} が含まれていても置き換えないよう、カタログを一度だけ走査する
+  pub fn format(self, locale: Locale, args: &[&str]) -> String {
+    let mut parts = self.get(locale).split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    let mut args = args.iter();
+    for part in parts {
+      // 引数が足りない場合は {} のまま残す
+      text.push_str(args.next().copied().unwrap_or("{}"));
+      text.push_str(part);
+    }
+    text
+  }
+}
+
+fn ja(text: Text) -> &'static str {
+  match text {
+    Text::SelectServer => "サーバーを選択してください",
+    Text::SelectMode => "モードを選択してください",
+    Text::SelectRank => "ランクを選択してください",
+    Text::SelectMember => "人数を選択してください",
+    Text::LastSettings => "前回の設定：{}",
+    Text::LastSettingsButton => "前回と同じ設定で作成",
+    Text::PresetPlaceholder => "プリセットから作成",
+    Text::Back => "戻る",
+    Text::Cancel => "キャンセル",
+    Text::MessageModalTitle => "募集メッセージ",
+    Text::MessageInputLabel => "募集メッセージを入力しましょう",
+    Text::MessageInputPlaceholder => "例: たくさん喋れる人募集！",
+    Text::EntryPanel => "# 募集を作成！\n下のボタンを押して、アンレート、コンペティティブ、カスタムの募集を作成しましょう！",
+    Text::CreateButton => "募集を作成",
+    Text::JoinButton => "参加する",
+    Text::LeaveButton => "参加をやめる",
+    Text::DeleteButton => "削除",
+    Text::CloseButton => "締め切る",
+    Text::Participants => "参加者",
+    Text::PanelServer => "サーバー：{}",
+    Text::PanelMode => "モード　：{}",
+    Text::PanelRank => "ランク　：{}",
+    Text::Filled => "{} 募集が埋まりました！",
+    Text::Joined => "募集に参加しました。",
+    Text::AlreadyJoined => "すでに参加しています。",
+    Text::Left => "募集参加を取り消しました。",
+    Text::CreatorLeave => "募集作成者は募集参加を取り消せません。\n募集を削除したい場合は「削除」ボタンを押してください。",
+    Text::NotJoined => "募集に参加していません。",
+    Text::Deleted => "募集を削除しました。",
+    Text::NotCreator => "募集作成者のみが削除できます。",
+    Text::Closed => "募集を締め切りました。",
+    Text::NotCreatorClose => "募集作成者のみが締め切れます。",
+    Text::RecruitmentClosed => "この募集は締め切られています。",
+    Text::PanelClosed => "({}/{}) 締め切りました",
+    Text::Expired => "期限切れの募集のため削除します。",
+    Text::PresetUnavailableMember => "{}では{}を選択できません。",
+    Text::PresetLimit => "プリセットは{}個まで保存できます。",
+    Text::PresetSaved => "プリセット「{}」を保存しました。\n{}",
+    Text::PresetEmpty => "保存されたプリセットはありません。",
+    Text::PresetListTitle => "プリセット一覧",
+    Text::PresetDeleted => "プリセット「{}」を削除しました。",
+    Text::PresetNotFound => "プリセット「{}」は存在しません。",
+    Text::LanguageSet => "このサーバーの表示言語を{}に設定しました。",
+    Text::LanguageReset => "このサーバーの表示言語を各ユーザーの言語設定に合わせます。",
+    Text::ShuttingDown => "Botを再起動しています。しばらくしてからもう一度お試しください。",
+    Text::SessionExpired => "この募集の作成は終了しています。もう一度「募集を作成」から始めてください。",
+    Text::ErrorDatabase => "データの読み書きに失敗しました。しばらくしてからもう一度お試しください。",
+    Text::ErrorDiscord => "Discordとの通信に失敗しました。しばらくしてからもう一度お試しください。",
+    Text::ErrorRecruitmentNotFound => "募集が見つかりません。すでに削除された可能性があります。",
+    Text::ErrorPresetNotFound => "プリセットが見つかりません。",
+    Text::ErrorPanelBroken => "募集パネルを読み取れません。お手数ですが募集を作り直してください。",
+    Text::ErrorInternal => "エラーが発生しました。",
+    Text::ErrorCode => "{}\n(エラーコード: {} / ID: {})",
+    Text::OpenRecruitments => "募集中",
+    Text::NoOpenRecruitments => "現在募集はありません。",
+    Text::SlotsLeft => "あと{}人",
+    Text::MoreRecruitments => "ほか{}件",
+    Text::ListTitle => "募集一覧 ({}/{})",
+    Text::NoMatchingRecruitments => "条件に合う募集はありません。",
+    Text::PreviousPage => "前へ",
+    Text::NextPage => "次へ",
+    Text::SelectLifetime => "募集の期限を選択してください",
+    Text::StartInputLabel => "開始時刻 (例: 21:00)",
+    Text::InvalidStartTime => "開始時刻は 21:00 のように入力してください。",
+    Text::StartTimeTooLate => "開始時刻がこのサーバーの募集の期限の上限を超えています。",
+    Text::PanelExpires => "締め切り：{}",
+    Text::LifetimeSet => "このサーバーの募集の期限を、既定値 {}、上限 {} に設定しました。",
+    Text::LifetimeDefaultTooLong => "既定値は上限以下にしてください。",
+    Text::NoLimit => "なし",
+  }
+}
+
+fn en(text: Text) -> &'static str {
+  match text {
+    Text::SelectServer => "Select a server",
+    Text::SelectMode => "Select a mode",
+    Text::SelectRank => "Select a rank",
+    Text::SelectMember => "Select the party size",
+    Text::LastSettings => "Last settings: {}",
+    Text::LastSettingsButton => "Create with last settings",
+    Text::PresetPlaceholder => "Create from a preset",
+    Text::Back => "Back",
+    Text::Cancel => "Cancel",
+    Text::MessageModalTitle => "Recruitment message",
+    Text::MessageInputLabel => "Enter a recruitment message",
+    Text::MessageInputPlaceholder => "e.g. Looking for chatty teammates!",
+    Text::EntryPanel => "# Create a recruitment!\nPress the button below to recruit for unrated, competitive or custom games!",
+    Text::CreateButton => "Create recruitment",
+    Text::JoinButton => "Join",
+    Text::LeaveButton => "Leave",
+    Text::DeleteButton => "Delete",
+    Text::CloseButton => "Close",
+    Text::Participants => "Participants",
+    Text::PanelServer => "Server: {}",
+    Text::PanelMode => "Mode: {}",
+    Text::PanelRank => "Rank: {}",
+    Text::Filled => "{} The party is full!",
+    Text::Joined => "You joined the recruitment.",
+    Text::AlreadyJoined => "You have already joined.",
+    Text::Left => "You left the recruitment.",
+    Text::CreatorLeave => "The creator cannot leave the recruitment.\nPress \"Delete\" if you want to remove it.",
+    Text::NotJoined => "You have not joined this recruitment.",
+    Text::Deleted => "The recruitment was deleted.",
+    Text::NotCreator => "Only the creator can delete this recruitment.",
+    Text::Closed => "The recruitment was closed.",
+    Text::NotCreatorClose => "Only the creator can close this recruitment.",
+    Text::RecruitmentClosed => "This recruitment is closed.",
+    Text::PanelClosed => "({}/{}) Closed",
+    Text::Expired => "This recruitment has expired and will be deleted.",
+    Text::PresetUnavailableMember => "In {}, {} is not available.",
+    Text::PresetLimit => "You can save up to {} presets.",
+    Text::PresetSaved => "Saved preset \"{}\".\n{}",
+    Text::PresetEmpty => "You have no saved presets.",
+    Text::PresetListTitle => "Presets",
+    Text::PresetDeleted => "Deleted preset \"{}\".",
+    Text::PresetNotFound => "Preset \"{}\" does not exist.",
+    Text::LanguageSet => "The display language of this server is now {}.",
+    Text::LanguageReset => "This server now follows each user's language setting.",
+    Text::ShuttingDown => "The bot is restarting. Please try again in a moment.",
+    Text::SessionExpired => "This recruitment draft has ended. Please start again from \"Create recruitment\".",
+    Text::ErrorDatabase => "Failed to read or write data. Please try again in a moment.",
+    Text::ErrorDiscord => "Failed to communicate with Discord. Please try again in a moment.",
+    Text::ErrorRecruitmentNotFound => "The recruitment was not found. It may have been deleted.",
+    Text::ErrorPresetNotFound => "The preset was not found.",
+    Text::ErrorPanelBroken => "The recruitment panel could not be read. Please create the recruitment again.",
+    Text::ErrorInternal => "Something went wrong.",
+    Text::ErrorCode => "{}\n(Error code: {} / ID: {})",
+    Text::OpenRecruitments => "Open recruitments",
+    Text::NoOpenRecruitments => "There are no open recruitments.",
+    Text::SlotsLeft => "{} left",
+    Text::MoreRecruitments => "and {} more",
+    Text::ListTitle => "Recruitments ({}/{})",
+    Text::NoMatchingRecruitments => "No recruitments match the conditions.",
+    Text::PreviousPage => "Previous",
+    Text::NextPage => "Next",
+    Text::SelectLifetime => "Select how long the recruitment stays open",
+    Text::StartInputLabel => "Start time (e.g. 21:00)",
+    Text::InvalidStartTime => "Enter the start time like 21:00.",
+    Text::StartTimeTooLate => "The start time exceeds the maximum recruitment lifetime of this server.",
+    Text::PanelExpires => "Closes: {}",
+    Text::LifetimeSet => "Recruitments in this server now default to {} with a maximum of {}.",
+    Text::LifetimeDefaultTooLong => "The default must not be longer than the maximum.",
+    Text::NoLimit => "none",
+  }
+}
+
+impl Handler {
+  // サーバーの言語設定があればそれを優先し、なければ user_locale を使う
+  // user_locale が None の場合 (募集パネル等の全員に見えるメッセージ) は日本語
+  pub async fn locale(&self, guild: Option<GuildId>, user_locale: Option<&str>) -> Locale {
+    let guild_locale = match guild {
+      Some(guild) => self.redis_client
+        .get_guild_locale(guild).await
+        .map_err(|e| e.log("Failed to get guild locale"))
+        .ok()
+        .flatten(),
+      None => None,
+    };
+    guild_locale.unwrap_or_else(|| user_locale.map_or(Locale::Ja, Locale::from_discord))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn format_replaces_placeholders_in_order() {
+    assert_eq!(Text::PresetUnavailableMember.format(Locale::Ja, &["カスタム", "10人"]), "カスタムでは10人を選択できません。");
+    assert_eq!(Text::PresetUnavailableMember.format(Locale::En, &["Competitive", "Quad"]), "In Competitive, Quad is not available.");
+  }
+
+  #[test]
+  fn format_keeps_placeholders_in_arguments() {
+    assert_eq!(Text::PresetUnavailableMember.format(Locale::En, &["{}", "Duo"]), "In {}, Duo is not available.");
+    assert_eq!(Text::PresetDeleted.format(Locale::Ja, &["a{}b"]), "プリセット「a{}b」を削除しました。");
+  }
+
+  #[test]
+  fn format_leaves_missing_arguments() {
+    assert_eq!(Text::PresetUnavailableMember.format(Locale::En, &["Custom"]), "In Custom, {} is not available.");
+  }
+}
@@ -0,0 +1,460 @@
+use redis::{AsyncTypedCommands, ValueType};
+use serenity::all::{GuildId, MessageId, UserId};
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{bot::{locale::Locale, types::{KeyLayout, RedisClient}}, error::BotError, metrics::TimedConnection};
+
+// Redisに保存するハッシュのスキーマバージョン
+// 1: 表示文言をそのまま保存 (versionフィールドなし)
+// 2: 識別子で保存
+// 3: キーを {prefix}:{guild}:recruit:{message} 等の名前空間付きのレイアウトに移動
+// 4: 募集中の募集の一覧 ({prefix}:{guild}:open) を追加
+// 5: 絞り込み用の索引 ({prefix}:{guild}:open:{field}:{value}) を追加
+pub const SCHEMA_VERSION: u32 = 5;
+
+// キーのレイアウトを変更したバージョン
+const NAMESPACED_KEYS_VERSION: u32 = 3;
+// 募集の索引を追加、変更したバージョン。索引は index_recruitments で作り直す
+const OPEN_INDEX_VERSION: u32 = 4;
+const INDEX_VERSION: u32 = 5;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+struct Migration {
+  // このマイグレーションを適用した後のバージョン
+  version: u32,
+  description: &'static str,
+  upgrade: fn(&mut HashMap<String, String>),
+}
+
+const MIGRATIONS: [Migration; 4] = [
+  Migration {
+    version: 2,
+    description: "表示文言を識別子に置き換え",
+    upgrade: legacy_values_to_ids,
+  },
+  // キーの移動は move_legacy_keys で行うため、ハッシュの内容は変わらない
+  Migration {
+    version: NAMESPACED_KEYS_VERSION,
+    description: "キーを名前空間付きのレイアウトに移動",
+    upgrade: |_| {},
+  },
+  Migration {
+    version: OPEN_INDEX_VERSION,
+    description: "募集中の募集の一覧を作成",
+    upgrade: |_| {},
+  },
+  Migration {
+    version: INDEX_VERSION,
+    description: "募集の絞り込み用の索引を作成",
+    upgrade: |_| {},
+  },
+];
+
+// 表示文言を識別子として保存していた頃の値と、現在の識別子の対応
+// 表示文言を変更してもこの表は変更しないこと
+const LEGACY_VALUES: [(&str, &str, &str); 27] = [
+  ("server", "Tokyo/東京 🇯🇵", "tokyo"),
+  ("server", "Hong Kong/香港 🇭🇰", "hong_kong"),
+  ("server", "Singapore/シンガポール 🇸🇬", "singapore"),
+  ("server", "Sydney/シドニー 🇦🇺", "sydney"),
+  ("server", "Mumbai/ムンバイ 🇮🇳", "mumbai"),
+  ("mode", "アンレート", "unrated"),
+  ("mode", "コンペティティブ", "competitive"),
+  ("mode", "カスタム", "custom"),
+  ("rank", "どこでも", "unranked"),
+  ("rank", "アイアン", "iron"),
+  ("rank", "ブロンズ", "bronze"),
+  ("rank", "シルバー", "silver"),
+  ("rank", "ゴールド", "gold"),
+  ("rank", "プラチナ", "platinum"),
+  ("rank", "ダイヤモンド", "diamond"),
+  ("rank", "アセンダント", "ascendant"),
+  ("rank", "イモータル", "immortal"),
+  ("rank", "レディアント", "radiant"),
+  ("member", "デュオ", "duo"),
+  ("member", "トリオ", "trio"),
+  ("member", "クアッド", "quad"),
+  ("member", "フルパ", "full_party"),
+  ("member", "6人", "six"),
+  ("member", "7人", "seven"),
+  ("member", "8人", "eight"),
+  ("member", "9人", "nine"),
+  ("member", "10人", "ten"),
+];
+
+fn legacy_values_to_ids(hash_set: &mut HashMap<String, String>) {
+  for (field, legacy, id) in LEGACY_VALUES {
+    if let Some(value) = hash_set.get_mut(field)
+      && value == legacy
+    {
+      *value = id.to_string();
+    }
+  }
+}
+
+// versionフィールドのないハッシュはバージョン1として扱う
+pub fn record_version(hash_set: &HashMap<String, String>) -> u32 {
+  hash_set.get("version")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1)
+}
+
+// ハッシュを最新のスキーマに変換する。変換した場合はtrueを返す
+pub fn upgrade_record(hash_set: &mut HashMap<String, String>) -> bool {
+  let version = record_version(hash_set);
+  if version >= SCHEMA_VERSION {
+    return false;
+  }
+  for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+    (migration.upgrade)(hash_set);
+  }
+  hash_set.insert("version".to_string(), SCHEMA_VERSION.to_string());
+  true
+}
+
+// マイグレーション対象のキー ({prefix}: より後ろ): 募集、前回の設定、プリセット
+fn is_record_key(key: &str) -> bool {
+  matches!(
+    key.splitn(4, ':').collect::<Vec<&str>>().as_slice(),
+    [_, "recruit", _] | ["user", _, "last_settings"] | ["user", _, "preset", _]
+  )
+}
+
+fn parse_id(s: &str) -> Option<u64> {
+  s.parse().ok().filter(|&id| id != 0)
+}
+
+// バージョン3より前のキー (プレフィックスより後ろ) の種類
+#[derive(Debug, PartialEq, Eq)]
+enum LegacyKey {
+  Recruit(MessageId),
+  // webhook_url, latest_entry
+  Guild(&'static str),
+  Locale(GuildId),
+  LastSettings(UserId),
+  Presets(UserId),
+  Preset(UserId, String),
+}
+
+impl LegacyKey {
+  // 名前空間付きのレイアウトでの移動先
+  fn destination(&self, layout: KeyLayout) -> String {
+    match self {
+      LegacyKey::Recruit(message) => layout.recruit_key(*message),
+      LegacyKey::Guild(name) => layout.guild_key(layout.guild, name),
+      LegacyKey::Locale(guild) => layout.guild_key(*guild, "locale"),
+      LegacyKey::LastSettings(user) => layout.user_key(*user, "last_settings"),
+      LegacyKey::Presets(user) => layout.user_key(*user, "presets"),
+      LegacyKey::Preset(user, name) => layout.user_key(*user, format!("preset:{}", name)),
+    }
+  }
+  // 最初のリリースがプレフィックスなしで保存していたキー
+  fn is_baseline(&self) -> bool {
+    matches!(self, LegacyKey::Recruit(_) | LegacyKey::Guild(_))
+  }
+}
+
+// プレフィックスなしのキーでSCANするパターン。最初のリリースの募集 (メッセージID)、webhook_url、latest_entry は常に移動する
+// それ以外の形のキーは他のアプリケーションのキーの場合があるため、unprefixed の場合だけ探す
+fn unprefixed_patterns(unprefixed: bool) -> &'static [&'static str] {
+  if unprefixed {
+    &["*"]
+  } else {
+    &["[1-9]*", "webhook_url", "latest_entry"]
+  }
+}
+
+// key の移動先。legacy_prefix は {prefix}: またはプレフィックスなしの場合は空文字列
+fn legacy_key(key: &str, legacy_prefix: &str, unprefixed: bool) -> Option<LegacyKey> {
+  let legacy = namespaced_key(key.strip_prefix(legacy_prefix)?)?;
+  (!legacy_prefix.is_empty() || unprefixed || legacy.is_baseline()).then_some(legacy)
+}
+
+fn namespaced_key(key: &str) -> Option<LegacyKey> {
+  if let Some(message) = parse_id(key) {
+    return Some(LegacyKey::Recruit(MessageId::new(message)));
+  }
+  if let Some(name) = ["webhook_url", "latest_entry"].into_iter().find(|&name| name == key) {
+    return Some(LegacyKey::Guild(name));
+  }
+  if let Some(guild) = key.strip_prefix("guild_locale:").and_then(parse_id) {
+    return Some(LegacyKey::Locale(GuildId::new(guild)));
+  }
+  if let Some(user) = key.strip_prefix("last_settings:").and_then(parse_id) {
+    return Some(LegacyKey::LastSettings(UserId::new(user)));
+  }
+  if let Some(user) = key.strip_prefix("presets:").and_then(parse_id) {
+    return Some(LegacyKey::Presets(UserId::new(user)));
+  }
+  let (user, name) = key.strip_prefix("preset:")?.split_once(':')?;
+  Some(LegacyKey::Preset(UserId::new(parse_id(user)?), name.to_string()))
+}
+
+// 他のアプリケーションのキーを移動しないよう、このBotが保存していた形式の値か確かめる
+async fn is_legacy_value(conn: &mut TimedConnection, key: &str, legacy: &LegacyKey) -> Result<bool, BotError> {
+  let value_type = conn.key_type(key).await?;
+  let required: &[&str] = match legacy {
+    LegacyKey::Recruit(_) => &["creator", "server", "mode", "member", "joined"],
+    LegacyKey::LastSettings(_) | LegacyKey::Preset(..) => &["server", "mode", "member"],
+    LegacyKey::Presets(_) => return Ok(value_type == ValueType::Set),
+    LegacyKey::Guild(_) | LegacyKey::Locale(_) => {
+      if value_type != ValueType::String {
+        return Ok(false);
+      }
+      let value = conn.get(key).await?.unwrap_or_default();
+      return Ok(match legacy {
+        LegacyKey::Guild("webhook_url") => value.starts_with("https://") && value.contains("/webhooks/"),
+        LegacyKey::Locale(_) => Locale::from_str(&value).is_ok(),
+        _ => parse_id(&value).is_some(),
+      });
+    }
+  };
+  if value_type != ValueType::Hash {
+    return Ok(false);
+  }
+  let fields = conn.hkeys(key).await?;
+  Ok(required.iter().all(|&field| fields.iter().any(|f| f == field)))
+}
+
+// バージョン3より前はプレフィックスなし、またはREDIS_KEY_PREFIXを付けただけのキーに保存していた
+async fn move_legacy_keys(redis_client: &RedisClient, unprefixed: bool) -> Result<usize, BotError> {
+  let mut conn = redis_client.connection.clone();
+  let prefixed = format!("{}:", redis_client.key_prefix);
+  let patterns = unprefixed_patterns(unprefixed).iter()
+    .map(|&pattern| (String::new(), pattern.to_string()))
+    .chain([(prefixed.clone(), format!("{}*", prefixed))]);
+  let mut candidates = Vec::new();
+  for (legacy_prefix, pattern) in patterns {
+    let mut iter = conn.scan_match::<_, String>(pattern).await?;
+    while let Some(key) = iter.next_item().await {
+      if let Some(legacy) = legacy_key(&key, &legacy_prefix, unprefixed) {
+        candidates.push((key, legacy));
+      }
+    }
+  }
+  let mut moved = 0;
+  for (key, legacy) in candidates {
+    if !is_legacy_value(&mut conn, &key, &legacy).await? {
+      tracing::warn!(key = %key, "Skipped a key that does not look like bot data");
+      continue;
+    }
+    // RENAMEは有効期限も引き継ぐ
+    conn.rename(&key, legacy.destination(redis_client.layout())).await?;
+    moved += 1;
+  }
+  Ok(moved)
+}
+
+// 索引を追加する前に作成された募集を索引に追加する
+async fn index_recruitments(redis_client: &RedisClient) -> Result<usize, BotError> {
+  let mut conn = redis_client.connection.clone();
+  let mut ids = Vec::new();
+  let prefix = redis_client.guild_key(redis_client.guild, "recruit:");
+  let mut iter = conn.scan_match::<_, String>(format!("{}*", prefix)).await?;
+  while let Some(key) = iter.next_item().await {
+    if let Some(id) = key.strip_prefix(&prefix).and_then(parse_id) {
+      ids.push(MessageId::new(id));
+    }
+  }
+  drop(iter);
+  let mut indexed = 0;
+  for id in ids {
+    match redis_client.get_webhook_data(id).await {
+      Ok(data) => redis_client.add_to_index(id, &data).await?,
+      // スキャン後に期限切れになった募集
+      Err(BotError::WebhookDataNotFound) => continue,
+      Err(e) => return Err(e),
+    }
+    indexed += 1;
+  }
+  Ok(indexed)
+}
+
+// 起動時と `valo-member-bot migrate` で実行する
+// 保存済みのスキーマバージョンが最新なら何もしない。unprefixed (MIGRATE_UNPREFIXED_KEYS) の場合は、
+// 後から有効にしても移動できるよう、最新でもプレフィックスなしのキーを探す
+pub async fn run(redis_client: &RedisClient, unprefixed: bool) -> Result<(), BotError> {
+  let mut conn = redis_client.connection.clone();
+  // バージョン3より前はプレフィックスなしで保存している場合がある。他のアプリケーションのキーの場合があるため削除はしない
+  let current = match conn.get(redis_client.global_key(SCHEMA_VERSION_KEY)).await? {
+    Some(version) => Some(version),
+    None if unprefixed => conn.get(SCHEMA_VERSION_KEY).await?,
+    None => None,
+  };
+  let current = current
+    .and_then(|v| v.parse::<u32>().ok())
+    .unwrap_or(1);
+  if current >= SCHEMA_VERSION && !unprefixed {
+    tracing::info!(version = current, "Redis schema is up to date");
+    return Ok(());
+  }
+  for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+    tracing::info!(version = migration.version, description = migration.description, "Applying Redis migration");
+  }
+  let mut moved = 0;
+  if current < NAMESPACED_KEYS_VERSION || unprefixed {
+    moved = move_legacy_keys(redis_client, unprefixed).await?;
+    tracing::info!(moved, "Moved Redis keys to namespaced layout");
+  }
+  // 移動した募集は索引に含まれていない
+  if current < INDEX_VERSION || moved > 0 {
+    let indexed = index_recruitments(redis_client).await?;
+    tracing::info!(indexed, "Indexed open recruitments");
+  }
+  let mut keys = Vec::new();
+  let prefix = redis_client.global_key("");
+  let mut iter = conn.scan_match::<_, String>(format!("{}*", prefix)).await?;
+  while let Some(key) = iter.next_item().await {
+    if key.strip_prefix(&prefix).is_some_and(is_record_key) {
+      keys.push(key);
+    }
+  }
+  drop(iter);
+  let mut migrated = 0;
+  for key in keys {
+    let mut hash_set = conn.hgetall(&key).await?;
+    // 期限切れ等でスキャン後に消えたキー
+    if hash_set.is_empty() || !upgrade_record(&mut hash_set) {
+      continue;
+    }
+    let fields_value: Vec<(&String, &String)> = hash_set.iter().collect();
+    conn.hset_multiple(&key, &fields_value).await?;
+    migrated += 1;
+  }
+  conn.set(redis_client.global_key(SCHEMA_VERSION_KEY), SCHEMA_VERSION).await?;
+  tracing::info!(from = current, to = SCHEMA_VERSION, migrated, "Redis schema migrated");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hash(fields: &[(&str, &str)]) -> HashMap<String, String> {
+    fields.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()
+  }
+
+  // 表示文言をそのまま保存していた頃の募集
+  fn v1_recruit() -> HashMap<String, String> {
+    hash(&[
+      ("creator", "123456789012345678"),
+      ("server", "Tokyo/東京 🇯🇵"),
+      ("mode", "コンペティティブ"),
+      ("rank", "ダイヤモンド"),
+      ("member", "フルパ"),
+      ("joined", "123456789012345678"),
+    ])
+  }
+
+  #[test]
+  fn record_version_defaults_to_1() {
+    assert_eq!(record_version(&v1_recruit()), 1);
+    assert_eq!(record_version(&hash(&[("version", "2")])), 2);
+    assert_eq!(record_version(&hash(&[("version", "broken")])), 1);
+  }
+
+  #[test]
+  fn legacy_values_to_ids_replaces_display_values() {
+    let mut hash_set = v1_recruit();
+    legacy_values_to_ids(&mut hash_set);
+    assert_eq!(hash_set["server"], "tokyo");
+    assert_eq!(hash_set["mode"], "competitive");
+    assert_eq!(hash_set["rank"], "diamond");
+    assert_eq!(hash_set["member"], "full_party");
+    assert_eq!(hash_set["creator"], "123456789012345678");
+  }
+
+  #[test]
+  fn legacy_values_to_ids_keeps_unknown_values() {
+    let mut hash_set = hash(&[("server", "tokyo"), ("rank", "None"), ("mode", "スイフトプレイ")]);
+    legacy_values_to_ids(&mut hash_set);
+    assert_eq!(hash_set, hash(&[("server", "tokyo"), ("rank", "None"), ("mode", "スイフトプレイ")]));
+  }
+
+  #[test]
+  fn upgrade_record_from_v1() {
+    let mut hash_set = v1_recruit();
+    assert!(upgrade_record(&mut hash_set));
+    assert_eq!(hash_set["server"], "tokyo");
+    assert_eq!(hash_set["member"], "full_party");
+    assert_eq!(record_version(&hash_set), SCHEMA_VERSION);
+  }
+
+  #[test]
+  fn upgrade_record_from_v2() {
+    let mut hash_set = hash(&[("server", "singapore"), ("mode", "unrated"), ("rank", "None"), ("member", "duo"), ("version", "2")]);
+    assert!(upgrade_record(&mut hash_set));
+    assert_eq!(hash_set["server"], "singapore");
+    assert_eq!(hash_set["rank"], "None");
+    assert_eq!(record_version(&hash_set), SCHEMA_VERSION);
+  }
+
+  #[test]
+  fn upgrade_record_keeps_current_version() {
+    let version = SCHEMA_VERSION.to_string();
+    let original = hash(&[("server", "mumbai"), ("mode", "custom"), ("member", "ten"), ("version", &version)]);
+    let mut hash_set = original.clone();
+    assert!(!upgrade_record(&mut hash_set));
+    assert_eq!(hash_set, original);
+  }
+
+  #[test]
+  fn is_record_key_matches_records_only() {
+    assert!(is_record_key("1:recruit:2"));
+    assert!(is_record_key("user:1:last_settings"));
+    assert!(is_record_key("user:1:preset:ランク"));
+    assert!(is_record_key("user:1:preset:a:b"));
+    assert!(!is_record_key("schema_version"));
+    assert!(!is_record_key("1:webhook_url"));
+    assert!(!is_record_key("1:open"));
+    assert!(!is_record_key("user:1:presets"));
+    assert!(!is_record_key("user:1:question_state:2"));
+  }
+
+  #[test]
+  fn namespaced_key_parses_legacy_keys() {
+    assert_eq!(namespaced_key("1234"), Some(LegacyKey::Recruit(MessageId::new(1234))));
+    assert_eq!(namespaced_key("webhook_url"), Some(LegacyKey::Guild("webhook_url")));
+    assert_eq!(namespaced_key("latest_entry"), Some(LegacyKey::Guild("latest_entry")));
+    assert_eq!(namespaced_key("guild_locale:5"), Some(LegacyKey::Locale(GuildId::new(5))));
+    assert_eq!(namespaced_key("last_settings:6"), Some(LegacyKey::LastSettings(UserId::new(6))));
+    assert_eq!(namespaced_key("presets:7"), Some(LegacyKey::Presets(UserId::new(7))));
+    assert_eq!(namespaced_key("preset:8:平日:夜"), Some(LegacyKey::Preset(UserId::new(8), "平日:夜".to_string())));
+  }
+
+  // 最初のリリースのキーは既定の設定でも名前空間付きのキーに移動する
+  #[test]
+  fn baseline_keys_move_by_default() {
+    let layout = KeyLayout { prefix: "valo", guild: GuildId::new(42) };
+    let keys = ["1234567890123", "webhook_url", "latest_entry", "guild_locale:42", "preset:1:a", "session:abc", "0"];
+    let moved: Vec<(&str, String)> = keys.iter()
+      .filter_map(|&key| Some((key, legacy_key(key, "", false)?.destination(layout))))
+      .collect();
+    assert_eq!(moved, vec![
+      ("1234567890123", "valo:42:recruit:1234567890123".to_string()),
+      ("webhook_url", "valo:42:webhook_url".to_string()),
+      ("latest_entry", "valo:42:latest_entry".to_string()),
+    ]);
+    // 既定のパターンで最初のリリースのキーがすべて見つかる
+    assert_eq!(unprefixed_patterns(false), &["[1-9]*", "webhook_url", "latest_entry"]);
+  }
+
+  #[test]
+  fn unprefixed_keys_of_other_shapes_need_opt_in() {
+    let layout = KeyLayout { prefix: "valo", guild: GuildId::new(42) };
+    assert_eq!(legacy_key("guild_locale:42", "", true).map(|k| k.destination(layout)), Some("valo:42:locale".to_string()));
+    assert_eq!(legacy_key("valo:preset:1:a", "valo:", false).map(|k| k.destination(layout)), Some("valo:user:1:preset:a".to_string()));
+    assert_eq!(legacy_key("other:1", "valo:", false), None);
+  }
+
+  #[test]
+  fn namespaced_key_ignores_other_keys() {
+    assert_eq!(namespaced_key("0"), None);
+    assert_eq!(namespaced_key("schema_version"), None);
+    assert_eq!(namespaced_key("guild_locale:abc"), None);
+    assert_eq!(namespaced_key("preset:0:name"), None);
+    assert_eq!(namespaced_key("preset:8"), None);
+    assert_eq!(namespaced_key("1:recruit:2"), None);
+  }
+}
@@ -1,66 +1,80 @@
-use serenity::all::{ButtonStyle, CacheHttp, ComponentInteraction, CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, Http, ReactionType};
+use serenity::{
+  all::{ButtonStyle, CacheHttp, ComponentInteraction, CreateActionRow, CreateButton, Http, MessageId, ReactionType},
+  http::HttpError,
+};
 
 mod send;
 mod edit;
 mod entry;
 mod delete;
 
-pub use entry::entry;
+pub use entry::{entry, refresh_entry, EntryDebounce};
 pub use send::send;
 pub use edit::edit;
 pub use delete::delete;
 
-use crate::{bot::types::{Rank, RedisClient}, config, error::BotError};
+use crate::{bot::{components::reply_ephemeral, custom_id::CustomId, locale::{Locale, Text}, types::{Rank, RedisClient}}, config::Config, metrics::METRICS};
 
-pub fn get_button(join_disable: bool) -> CreateActionRow {
+// 締め切った募集はすべてのボタンを無効にする
+pub fn get_button(join_disable: bool, closed: bool, locale: Locale) -> CreateActionRow {
   let buttons = vec![
-    CreateButton::new("参加する")
-      .label("参加する")
+    CreateButton::new(CustomId::Join)
+      .label(Text::JoinButton.get(locale))
       .style(ButtonStyle::Secondary)
       .emoji(ReactionType::Unicode("✋".to_string()))
-      .disabled(join_disable),
-    CreateButton::new("参加をやめる")
-      .label("参加をやめる")
+      .disabled(join_disable || closed),
+    CreateButton::new(CustomId::Leave)
+      .label(Text::LeaveButton.get(locale))
       .style(ButtonStyle::Secondary)
       .emoji(ReactionType::Unicode("👋".to_string()))
-      .disabled(join_disable),
-    CreateButton::new("削除")
-      .label("削除")
+      .disabled(join_disable || closed),
+    CreateButton::new(CustomId::Close)
+      .label(Text::CloseButton.get(locale))
+      .style(ButtonStyle::Secondary)
+      .emoji(ReactionType::Unicode("🔒".to_string()))
+      .disabled(closed),
+    // 締め切った募集パネルも作成者が削除できるよう、削除ボタンは有効のままにする
+    CreateButton::new(CustomId::Delete)
+      .label(Text::DeleteButton.get(locale))
       .style(ButtonStyle::Secondary)
       .emoji(ReactionType::Unicode("🚫".to_string())),
   ];
   CreateActionRow::Buttons(buttons)
 }
 
-pub fn get_thumbnail(rank: Option<Rank>) -> Result<String, BotError> {
-  let base_url = config::get("BASE_IMG_URL")?;
+pub fn get_thumbnail(base_url: &str, rank: Option<Rank>) -> String {
   match rank {
-    Some(Rank::Radiant) => Ok(format!("{}radiant.png", base_url)),
-    Some(Rank::Immortal) => Ok(format!("{}immortal.png", base_url)),
-    Some(Rank::Ascendant) => Ok(format!("{}ascendant.png", base_url)),
-    Some(Rank::Diamond) => Ok(format!("{}diamond.png", base_url)),
-    Some(Rank::Platinum) => Ok(format!("{}platinum.png", base_url)),
-    Some(Rank::Gold) => Ok(format!("{}gold.png", base_url)),
-    Some(Rank::Silver) => Ok(format!("{}silver.png", base_url)),
-    Some(Rank::Bronze) => Ok(format!("{}bronze.png", base_url)),
-    Some(Rank::Iron) => Ok(format!("{}iron.png", base_url)),
-    _ => Ok(format!("{}unrated.png", base_url)),
+    Some(Rank::Radiant) => format!("{}radiant.png", base_url),
+    Some(Rank::Immortal) => format!("{}immortal.png", base_url),
+    Some(Rank::Ascendant) => format!("{}ascendant.png", base_url),
+    Some(Rank::Diamond) => format!("{}diamond.png", base_url),
+    Some(Rank::Platinum) => format!("{}platinum.png", base_url),
+    Some(Rank::Gold) => format!("{}gold.png", base_url),
+    Some(Rank::Silver) => format!("{}silver.png", base_url),
+    Some(Rank::Bronze) => format!("{}bronze.png", base_url),
+    Some(Rank::Iron) => format!("{}iron.png", base_url),
+    _ => format!("{}unrated.png", base_url),
   }
 }
 
-pub async fn handle_expired<T>(http: T, component: &ComponentInteraction, redis_client: &mut RedisClient)
+// message は期限切れの募集パネル。/list から操作した場合は component.message と異なる
+pub async fn handle_expired<T>(http: T, config: &Config, component: &ComponentInteraction, message: MessageId, redis_client: &RedisClient, locale: Locale)
 where
   T: AsRef<Http> + CacheHttp + Copy,
 {
-  component.create_response(http, CreateInteractionResponse::Message(
-    CreateInteractionResponseMessage::new()
-      .content("期限切れの募集のため削除します。")
-      .ephemeral(true)
-    ))
-    .await
-    .map_err(|e| tracing::warn!(error = %e, "Failed to create join response"))
+  METRICS.expiries.inc(&[]);
+  redis_client.delete_webhook_data(message).await
+    .map_err(|e| e.log("Failed to delete expired recruitment"))
+    .ok();
+  reply_ephemeral(http, component, Text::Expired.get(locale)).await
+    .map_err(|e| e.log("Failed to create expired response"))
     .ok();
-  self::delete(http, redis_client, component.message.id).await
-    .map_err(|e| tracing::warn!(error = %e, "Failed to delete expired panel"))
+  self::delete(http, config, redis_client, message).await
+    .map_err(|e| e.log("Failed to delete expired panel"))
     .ok();
 }
+
+// 削除済みのメッセージを操作したときのエラー (Unknown Message)
+pub fn is_unknown_message(error: &serenity::Error) -> bool {
+  matches!(error, serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) if response.error.code == 10008)
+}
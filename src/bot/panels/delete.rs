@@ -1,9 +1,12 @@
 use serenity::all::{CacheHttp, Http, MessageId};
 
-use crate::{bot::types::RedisClient, error::BotError};
+use crate::{bot::{panels::is_unknown_message, types::RedisClient}, config::Config, error::BotError, metrics::time_discord};
 
-pub async fn delete<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut RedisClient, message: MessageId) -> Result<(), BotError> {
-  let webhook = redis_client.get_webhook(http);
-  webhook.await?.delete_message(http, None, message).await?;
-  Ok(())
+pub async fn delete<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, redis_client: &RedisClient, message: MessageId) -> Result<(), BotError> {
+  let webhook = redis_client.get_webhook(http, config.channel_id);
+  match time_discord("delete_webhook_message", webhook.await?.delete_message(http, None, message)).await {
+    // 手動で削除された場合など
+    Err(e) if is_unknown_message(&e) => Ok(()),
+    result => Ok(result?),
+  }
 }
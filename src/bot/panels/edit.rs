@@ -1,11 +1,11 @@
 use serenity::all::{CacheHttp, CreateEmbed, EditWebhookMessage, Http, MessageId};
 
-use crate::{bot::{panels::get_button, types::RedisClient}, error::BotError};
+use crate::{bot::{colors::CLOSED_COLOR, locale::{Locale, Text}, panels::get_button, types::RedisClient}, config::Config, error::BotError, metrics::time_discord};
 
-pub async fn edit<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut RedisClient, message: MessageId)  -> Result<bool, BotError>{
+pub async fn edit<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, redis_client: &RedisClient, message: MessageId, locale: Locale) -> Result<bool, BotError> {
   let webhook_data = redis_client.get_webhook_data(message).await?;
-  let webhook = redis_client.get_webhook(http).await?;
-  let old_message = webhook.get_message(http, None, message).await?;
+  let webhook = redis_client.get_webhook(http, config.channel_id).await?;
+  let old_message = time_discord("get_webhook_message", webhook.get_message(http, None, message)).await?;
   let embed = old_message.embeds.first().cloned().ok_or(BotError::EmbedBroken("embed"))?;
   let joined_users = webhook_data
     .joined
@@ -13,17 +13,26 @@ pub async fn edit<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut
     .map(|&u| format!("<@{}>", u.get()))
     .collect::<Vec<String>>()
     .join("\n");
+  let count = webhook_data.joined.len().to_string();
+  let member = u8::from(webhook_data.member).to_string();
+  let closed = webhook_data.closed_at.is_some();
+  // 締め切った募集は灰色にして、参加者の一覧は残す
+  let (title, color) = if closed {
+    (Text::PanelClosed.format(locale, &[&count, &member]), CLOSED_COLOR)
+  } else {
+    (format!("({}/{})", count, member), embed.colour.ok_or(BotError::EmbedBroken("color"))?.0)
+  };
   let new_embed = CreateEmbed::new()
-    .title(format!("({}/{})",webhook_data.joined.len(), u8::from(webhook_data.member)))
-    .color(embed.colour.ok_or(BotError::EmbedBroken("color"))?)
+    .title(title)
+    .color(color)
     .description(embed.description.ok_or(BotError::EmbedBroken("description"))?)
     .thumbnail(embed.thumbnail.map_or(String::new(), |t| t.url))
-    .field("参加者", joined_users, false);
+    .field(Text::Participants.get(locale), joined_users, false);
   let mut new_message = EditWebhookMessage::new()
     .embed(new_embed);
   let is_fill = webhook_data.joined.len() == u8::from(webhook_data.member) as usize;
-  let new_buttons = get_button(is_fill);
+  let new_buttons = get_button(is_fill, closed, locale);
   new_message = new_message.components(vec![new_buttons]);
-  webhook.edit_message(http, message, new_message).await?;
+  time_discord("edit_webhook_message", webhook.edit_message(http, message, new_message)).await?;
   Ok(is_fill)
 }
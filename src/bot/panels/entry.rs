@@ -1,43 +1,157 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use redis::AsyncTypedCommands;
-use serenity::all::{ButtonStyle, CacheHttp, ChannelId, CreateActionRow, CreateButton, CreateEmbed, CreateMessage, Http, MessageId};
+use serenity::all::{ButtonStyle, CacheHttp, CreateActionRow, CreateButton, CreateEmbed, CreateMessage, EditMessage, GetMessages, Http, MessageId};
+use tokio::{sync::{Mutex, MutexGuard}, time::Instant};
 
-use crate::{bot::{colors::PIN_MESSAGE_COLOR, types::RedisClient}, config, error::BotError};
+use crate::{bot::{colors::PIN_MESSAGE_COLOR, custom_id::CustomId, locale::{Locale, Text}, panels::is_unknown_message, types::{RecruitmentFilter, RedisClient}}, config::Config, error::BotError, metrics::time_discord};
 
-pub async fn entry<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut RedisClient) -> Result<(), BotError> {
-  delete_latest(http, redis_client).await?;
-  let embed = CreateEmbed::new()
-    .description("# 募集を作成！\n下のボタンを押して、アンレート、コンペティティブ、カスタムの募集を作成しましょう！")
-    .color(PIN_MESSAGE_COLOR);
-  let entry_panel = CreateMessage::new()
-    .embed(embed)
-    .components(vec![CreateActionRow::Buttons(vec![
-      CreateButton::new("募集を作成")
-        .style(ButtonStyle::Secondary)
-        .label("募集を作成")
-    ])]);
-  let channel = ChannelId::from_str(&config::get("CHANNEL_ID")?)?;
-  let latest_entry = channel.send_message(http, entry_panel).await?;
-  let mut conn = redis_client.connection.lock().await;
-  conn.set("latest_entry", latest_entry.id.get()).await?;
-  drop(conn);
-  Ok(())
-}
-
-async fn delete_latest<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut RedisClient) -> Result<(), BotError> {
-  let mut conn = redis_client.connection.lock().await;
-  let channel = ChannelId::from_str(&config::get("CHANNEL_ID")?)?;
-  match conn.get("latest_entry").await {
-    Ok(Some(message_id)) => {
-      drop(conn);
-      let message = MessageId::from_str(&message_id)?;
-      channel.delete_message(http, message).await?;
-      return Ok(());
+// 最後のメッセージからこの時間発言がなければ再投稿する
+const DEBOUNCE: Duration = Duration::from_secs(3);
+// 発言が続いていても、最初のメッセージからこの時間が経てば再投稿する
+const MAX_DELAY: Duration = Duration::from_secs(15);
+// 募集作成パネルが直近のこの件数のメッセージに含まれていれば再投稿しない
+const RECENT_MESSAGES: u8 = 5;
+// 募集作成パネルに表示する募集中の募集の数
+const SUMMARY_LIMIT: usize = 10;
+
+// チャンネルへの発言ごとの募集作成パネルの再投稿を1回にまとめる
+#[derive(Default)]
+pub struct EntryDebounce {
+  // 予約中の再投稿の、最初と最後のメッセージの時刻
+  pending: Mutex<Option<(Instant, Instant)>>,
+  // 再投稿は同時に1つだけ実行する
+  running: Mutex<()>,
+}
+
+impl EntryDebounce {
+  // 再投稿を予約する。予約済みならその予約にまとめてfalseを返す
+  pub async fn schedule(&self) -> bool {
+    let now = Instant::now();
+    let mut pending = self.pending.lock().await;
+    match pending.as_mut() {
+      Some((_, last)) => {
+        *last = now;
+        false
+      }
+      None => {
+        *pending = Some((now, now));
+        true
+      }
+    }
+  }
+  // 発言が落ち着くまで待ってから予約を消化する。ガードを持っている間は次の再投稿を始めない
+  pub async fn wait(&self) -> MutexGuard<'_, ()> {
+    loop {
+      let mut pending = self.pending.lock().await;
+      let Some((first, last)) = *pending else {
+        break;
+      };
+      let deadline = (last + DEBOUNCE).min(first + MAX_DELAY);
+      if Instant::now() >= deadline {
+        // 以降のメッセージは次の予約になる
+        *pending = None;
+        break;
+      }
+      drop(pending);
+      tokio::time::sleep_until(deadline).await;
     }
-    _ => {
-      drop(conn);
-      return Ok(());
+    self.running.lock().await
+  }
+}
+
+// 募集作成パネルを再投稿してチャンネルの一番下に表示する。再投稿しなかった場合はfalse
+pub async fn entry<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, redis_client: &RedisClient, locale: Locale) -> Result<bool, BotError> {
+  let latest = latest_entry(redis_client).await?;
+  if let Some(latest) = latest {
+    if is_recent(http, config, latest).await? {
+      return Ok(false);
     }
+    delete_latest(http, config, latest).await?;
+  }
+  let (embeds, components) = entry_panel(config, redis_client, locale).await?;
+  let entry_panel = CreateMessage::new()
+    .embeds(embeds)
+    .components(components);
+  let latest_entry = time_discord("send_message", config.channel_id.send_message(http, entry_panel)).await?;
+  let mut conn = redis_client.connection.clone();
+  conn.set(redis_client.guild_key(redis_client.guild, "latest_entry"), latest_entry.id.get()).await?;
+  Ok(true)
+}
+
+// 募集の一覧が変わったときに、再投稿せずに募集作成パネルを更新する
+pub async fn refresh_entry<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, redis_client: &RedisClient, locale: Locale) -> Result<(), BotError> {
+  let Some(latest) = latest_entry(redis_client).await? else {
+    return Ok(());
+  };
+  let (embeds, components) = entry_panel(config, redis_client, locale).await?;
+  let entry_panel = EditMessage::new()
+    .embeds(embeds)
+    .components(components);
+  match time_discord("edit_message", config.channel_id.edit_message(http, latest, entry_panel)).await {
+    // 再投稿中に古いパネルが削除された場合など
+    Err(e) if is_unknown_message(&e) => Ok(()),
+    result => result.map(|_| ()).map_err(Into::into),
+  }
+}
+
+async fn entry_panel(config: &Config, redis_client: &RedisClient, locale: Locale) -> Result<(Vec<CreateEmbed>, Vec<CreateActionRow>), BotError> {
+  let embed = CreateEmbed::new()
+    .description(Text::EntryPanel.get(locale))
+    .color(PIN_MESSAGE_COLOR);
+  let summary = CreateEmbed::new()
+    .title(Text::OpenRecruitments.get(locale))
+    .description(summary(config, redis_client, locale).await?)
+    .color(PIN_MESSAGE_COLOR);
+  let components = vec![CreateActionRow::Buttons(vec![
+    CreateButton::new(CustomId::Create)
+      .style(ButtonStyle::Secondary)
+      .label(Text::CreateButton.get(locale))
+  ])];
+  Ok((vec![embed, summary], components))
+}
+
+// 空きのある募集を新しい順に、募集パネルへのリンク付きで並べる
+async fn summary(config: &Config, redis_client: &RedisClient, locale: Locale) -> Result<String, BotError> {
+  let ids = redis_client.find_recruitments(&RecruitmentFilter { has_slots: true, ..Default::default() }).await?;
+  // 「ほか N件」に読み出せない募集を含めないよう、すべて読み出してから数える
+  let open = redis_client.get_recruitments(&ids).await?;
+  if open.is_empty() {
+    return Ok(Text::NoOpenRecruitments.get(locale).to_string());
+  }
+  let mut lines: Vec<String> = open.iter()
+    .take(SUMMARY_LIMIT)
+    .map(|(id, data)| format!(
+      "[{}]({}) ・ {}",
+      data.summary(locale),
+      id.link(config.channel_id, Some(redis_client.guild)),
+      Text::SlotsLeft.format(locale, &[&data.slots_left().to_string()]),
+    ))
+    .collect();
+  if open.len() > SUMMARY_LIMIT {
+    lines.push(Text::MoreRecruitments.format(locale, &[&(open.len() - SUMMARY_LIMIT).to_string()]));
+  }
+  Ok(lines.join("\n"))
+}
+
+async fn latest_entry(redis_client: &RedisClient) -> Result<Option<MessageId>, BotError> {
+  let mut conn = redis_client.connection.clone();
+  match conn.get(redis_client.guild_key(redis_client.guild, "latest_entry")).await? {
+    Some(message_id) => Ok(Some(MessageId::from_str(&message_id)?)),
+    None => Ok(None),
+  }
+}
+
+// 直近のメッセージに募集作成パネルがあるか
+async fn is_recent<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, latest: MessageId) -> Result<bool, BotError> {
+  let messages = time_discord("get_messages", config.channel_id.messages(http, GetMessages::new().limit(RECENT_MESSAGES))).await?;
+  Ok(messages.iter().any(|message| message.id == latest))
+}
+
+async fn delete_latest<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, latest: MessageId) -> Result<(), BotError> {
+  match time_discord("delete_message", config.channel_id.delete_message(http, latest)).await {
+    // 手動で削除された場合など
+    Err(e) if is_unknown_message(&e) => Ok(()),
+    result => Ok(result?),
   }
 }
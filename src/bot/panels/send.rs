@@ -4,34 +4,38 @@ use serenity::all::{
 
 use crate::{
   bot::{
-    colors::BASE_COLOR, panels::{get_button, get_thumbnail}, types::{
+    colors::BASE_COLOR, locale::{Locale, Text}, panels::{get_button, get_thumbnail}, types::{
       RedisClient, WebhookData, WebhookDataExt
     }
   },
-  error::BotError
+  config::Config,
+  error::BotError,
+  metrics::{time_discord, METRICS},
 };
 
-pub async fn send<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut RedisClient, webhook_data: &WebhookData, cont: Option<&str>) -> Result<(), BotError> {
-  let webhook = redis_client.get_webhook(http);
+pub async fn send<T: AsRef<Http> + CacheHttp + Copy>(http: T, config: &Config, redis_client: &RedisClient, webhook_data: &WebhookData, cont: Option<&str>, locale: Locale) -> Result<(), BotError> {
+  let webhook = redis_client.get_webhook(http, config.channel_id);
   let joined_users: String = webhook_data.joined
     .iter()
     .map(|&u| format!("<@{}>", u.get()))
     .collect::<Vec<String>>()
     .join("\n");
-  let thumbail = get_thumbnail(webhook_data.rank)?;
+  let thumbail = get_thumbnail(&config.base_img_url, webhook_data.rank);
   let embed = CreateEmbed::new()
     .title(format!("({}/{})", webhook_data.joined.len(), u8::from(webhook_data.member)))
     .color(webhook_data.rank.map_or(BASE_COLOR, |r| r.to_color()))
     .description(format!(
-      "サーバー：{}\nモード　：{}{}",
-      webhook_data.server.as_str(),
-      webhook_data.mode.as_str(),
-      webhook_data.rank.map_or(String::new(), |r| format!("\nランク　：{}", r.as_str()))
+      "{}\n{}{}{}",
+      Text::PanelServer.format(locale, &[webhook_data.server.label(locale)]),
+      Text::PanelMode.format(locale, &[webhook_data.mode.label(locale)]),
+      webhook_data.rank.map_or(String::new(), |r| format!("\n{}", Text::PanelRank.format(locale, &[r.label(locale)]))),
+      // Discordのタイムスタンプ記法で、閲覧者ごとに「あと1時間」のように表示される
+      webhook_data.expires_at.map_or(String::new(), |t| format!("\n{}", Text::PanelExpires.format(locale, &[&format!("<t:{}:R>", t)]))),
     ))
     .thumbnail(thumbail)
-    .field("参加者", joined_users, false);
-  let buttons = get_button(false);
-  let creator = webhook_data.creator.to_user(http).await?;
+    .field(Text::Participants.get(locale), joined_users, false);
+  let buttons = get_button(false, false, locale);
+  let creator = time_discord("get_user", webhook_data.creator.to_user(http)).await?;
   let mut webhook_message = ExecuteWebhook::new()
     .username(creator.display_name())
     .avatar_url(creator.face())
@@ -44,7 +48,12 @@ pub async fn send<T: AsRef<Http> + CacheHttp + Copy>(http: T, redis_client: &mut
   // 第2引数がtrueのため必ずSomeを返す
   // 詳細: https://docs.rs/serenity/latest/serenity/http/struct.Http.html#method.execute_webhook
   // Webhook::execute() -> ExecuteWebhook::execute() -> Http::execute_webhook()のラッパー
-  let message = webhook.await?.execute(http, true, webhook_message).await?.unwrap();
+  let message = time_discord("execute_webhook", webhook.await?.execute(http, true, webhook_message)).await?.unwrap();
   redis_client.store_webhook_data(message.id, webhook_data).await?;
+  // 募集は投稿済みのため、前回の設定を保存できなくてもエラーにしない
+  if let Err(e) = redis_client.store_last_settings(webhook_data.creator, webhook_data).await {
+    e.log("Failed to store last settings");
+  }
+  METRICS.recruitments.inc(&[webhook_data.mode.as_str(), webhook_data.server.as_str()]);
   Ok(())
 }
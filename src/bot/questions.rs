@@ -1,57 +1,173 @@
+mod lifetime;
 mod member;
 mod message;
 mod mode;
 mod rank;
 mod server;
 
+pub use lifetime::start_time;
+pub use message::message_modal;
+
 use crate::{
-  bot::{types::{RedisClient, WebhookData}, Handler},
+  bot::{custom_id::{CustomId, SessionId}, locale::{Locale, Text}, types::{WebhookData, QUESTION_STATE_EXPIRY_SECONDS}, Handler},
   error::BotError,
 };
-use serenity::all::{ComponentInteraction, UserId};
+use serenity::all::{ButtonStyle, CacheHttp, ComponentInteraction, CreateActionRow, CreateButton, EditInteractionResponse, Http, ReactionType, Timestamp, UserId};
 
 // 質問フロー内でデータ作成、編集等に使用するメソッドを実装
 impl Handler {
-  pub async fn create(&self, id: UserId) {
+  pub async fn create(&self, session: SessionId, user: UserId) {
+    self.start_session(session, WebhookData::new(user)).await;
+  }
+  // 新しいフローを開始する。放置されたフローが溜まらないよう、開始時に期限切れのセッションを取り除く
+  pub async fn start_session(&self, session: SessionId, data: WebhookData) {
+    self.sweep_sessions().await;
     let mut lock = self.question_state.lock().await;
-    let data = WebhookData::new(id);
-    lock.insert(id, data);
+    lock.insert(session, data);
+  }
+  // インタラクションのトークンの有効期限を過ぎたセッションを取り除く
+  async fn sweep_sessions(&self) {
+    let now = Timestamp::now().unix_timestamp();
+    let mut question_state = self.question_state.lock().await;
+    let before = question_state.len();
+    question_state.retain(|&session, _| !is_expired(session, now));
+    let swept = before - question_state.len();
+    drop(question_state);
+    self.component_store.lock().await.retain(|&session, _| !is_expired(session, now));
+    if swept > 0 {
+      tracing::debug!(swept, "Removed expired question sessions");
+    }
   }
-  pub async fn set<F: FnMut(&mut WebhookData)>(&self, id: UserId, mut f: F) {
+  pub async fn set<F: FnMut(&mut WebhookData)>(&self, session: SessionId, mut f: F) {
     let mut lock = self.question_state.lock().await;
-    if let Some(data) = lock.get_mut(&id) {
+    if let Some(data) = lock.get_mut(&session) {
       f(data);
     } else {
-      tracing::warn!("No data found for user: {}", id);
+      tracing::warn!(%session, "No data found for session");
     }
   }
-  pub async fn get_question_state(&self, id: UserId) -> Result<WebhookData, BotError> {
+  pub async fn get_question_state(&self, session: SessionId) -> Result<WebhookData, BotError> {
     let lock = self.question_state.lock().await;
-    if let Some(data) = lock.get(&id) {
-      Ok(data.clone())
-    } else {
-      Err(BotError::WebhookDataNotFound)
-    }
+    lock.get(&session).cloned().ok_or(BotError::SessionNotFound)
   }
-  pub async fn get_component(&self, id: UserId) -> Option<ComponentInteraction> {
-    let lock = self.component_store.lock().await;
-    lock.get(&id).cloned()
+  // 作成中のセッションで、userが作成者のときtrue
+  pub async fn is_active_session(&self, session: SessionId, user: UserId) -> bool {
+    let lock = self.question_state.lock().await;
+    lock.get(&session).is_some_and(|data| data.creator == user)
   }
-  pub async fn get_redis_client(&self) -> RedisClient {
-    let lock = self.redis_client.lock().await;
-    lock.clone()
+  pub async fn get_component(&self, session: SessionId) -> Option<ComponentInteraction> {
+    let lock = self.component_store.lock().await;
+    lock.get(&session).cloned()
   }
-  pub async fn remove_temp_data(&self, id: UserId) -> Result<(), BotError> {
+  pub async fn remove_temp_data(&self, session: SessionId) -> Result<(), BotError> {
     let mut lock = self.question_state.lock().await;
-    if lock.remove(&id).is_none() {
+    if lock.remove(&session).is_none() {
       drop(lock);
-      return Err(BotError::WebhookDataNotFound);
+      return Err(BotError::SessionNotFound);
     }
+    // /preset use から始まったフローではコンポーネントが保存されていない
     let mut lock = self.component_store.lock().await;
-    if lock.remove(&id).is_none() {
-      drop(lock);
-      return Err(BotError::ComponentInteractionNotFound);
-    }
+    lock.remove(&session);
     Ok(())
   }
+  // 停止時に作成途中の質問状態をRedisに保存する
+  pub async fn persist_question_state(&self) -> Result<usize, BotError> {
+    self.sweep_sessions().await;
+    let states = self.question_state.lock().await.clone();
+    for (session, data) in &states {
+      self.redis_client.store_question_state(*session, data).await?;
+    }
+    Ok(states.len())
+  }
+  // 起動時に前回の停止時に保存した質問状態を読み込む。質問を表示するコンポーネントは
+  // 再起動後に最初に操作されたときに keep_component で登録する
+  pub async fn restore_question_state(&self) -> Result<usize, BotError> {
+    let now = Timestamp::now().unix_timestamp();
+    let states: Vec<_> = self.redis_client.take_question_states().await?
+      .into_iter()
+      .filter(|&(session, _)| !is_expired(session, now))
+      .collect();
+    let count = states.len();
+    let mut question_state = self.question_state.lock().await;
+    question_state.extend(states);
+    Ok(count)
+  }
+  // 再起動で失われたコンポーネントの代わりに、同じメッセージ上で操作されたコンポーネントで次の質問を表示する
+  pub async fn keep_component(&self, session: SessionId, comp: &ComponentInteraction) {
+    let mut lock = self.component_store.lock().await;
+    lock.entry(session).or_insert_with(|| comp.clone());
+  }
+  // 前回の設定を質問状態に読み込み、サーバー〜人数の選択を飛ばしてメッセージ入力に進む
+  pub async fn last_settings<T>(&self, http: T, comp: &ComponentInteraction, session: SessionId, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let mut data = self.redis_client
+      .get_last_settings(comp.user.id).await?
+      .ok_or(BotError::WebhookDataNotFound)?;
+    self.clamp_lifetime(&mut data).await?;
+    let mut lock = self.question_state.lock().await;
+    lock.insert(session, data);
+    drop(lock);
+    self.message(http, comp, session, None, locale).await
+  }
+  // 作成フロー冒頭のプリセット選択から、プリセットの設定とメッセージでメッセージ入力に進む
+  pub async fn use_preset<T>(&self, http: T, comp: &ComponentInteraction, session: SessionId, name: &str, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let mut preset = self.redis_client
+      .get_preset(comp.user.id, name).await?
+      .ok_or(BotError::PresetNotFound)?;
+    self.clamp_lifetime(&mut preset.data).await?;
+    let mut lock = self.question_state.lock().await;
+    lock.insert(session, preset.data);
+    drop(lock);
+    self.message(http, comp, session, preset.message.as_deref(), locale).await
+  }
+  // 「募集を作成」で表示したエフェメラルメッセージを編集して次の質問を表示する
+  async fn edit_question<T>(&self, http: T, session: SessionId, response: EditInteractionResponse) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let component_store = self.component_store.lock().await;
+    if let Some(comp) = component_store.get(&session) {
+      comp.edit_response(http, response).await?;
+      Ok(())
+    } else {
+      Err(BotError::ComponentInteractionNotFound)
+    }
+  }
+  pub async fn cancel<T>(&self, http: T, session: SessionId) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    if let Some(comp) = self.get_component(session).await {
+      comp.delete_response(http).await?;
+    }
+    self.remove_temp_data(session).await
+  }
+}
+
+fn is_expired(session: SessionId, now: i64) -> bool {
+  now - session.started_at() >= QUESTION_STATE_EXPIRY_SECONDS
+}
+
+// back: 戻るボタンのcustom_id (最初の質問ではNone)
+fn navigation(session: SessionId, back: Option<CustomId>, locale: Locale) -> CreateActionRow {
+  let mut buttons = Vec::new();
+  if let Some(id) = back {
+    buttons.push(
+      CreateButton::new(id.session(session))
+        .label(Text::Back.get(locale))
+        .style(ButtonStyle::Secondary)
+        .emoji(ReactionType::Unicode("↩️".to_string()))
+    );
+  }
+  buttons.push(
+    CreateButton::new(CustomId::Cancel.session(session))
+      .label(Text::Cancel.get(locale))
+      .style(ButtonStyle::Danger)
+  );
+  CreateActionRow::Buttons(buttons)
 }
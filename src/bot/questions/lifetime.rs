@@ -0,0 +1,56 @@
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serenity::all::{CacheHttp, CreateActionRow, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http};
+
+use crate::{bot::{colors::BASE_COLOR, custom_id::{CustomId, SessionId}, locale::{Locale, Text}, questions::navigation, types::{Lifetime, WebhookData, WebhookDataExt}, Handler}, error::BotError};
+
+impl Handler {
+  // selected が None の場合はサーバーの既定値を選択しておく
+  pub async fn lifetime<T>(&self, http: T, session: SessionId, selected: Option<Lifetime>, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let settings = self.redis_client.get_lifetime_settings(self.redis_client.guild).await?;
+    let selected = selected.unwrap_or(settings.default);
+    let embed = CreateEmbed::new()
+      .title(Text::SelectLifetime.get(locale))
+      .color(BASE_COLOR);
+    // 上限を超える期限は選べない。開始時刻までの場合は入力された時刻で確認する
+    let options = Lifetime::variants()
+      .filter(|lifetime| lifetime.seconds().is_none_or(|s| s <= settings.max_seconds))
+      .map(|lifetime| {
+        CreateSelectMenuOption::new(lifetime.label(locale), lifetime.as_str())
+          .default_selection(selected == lifetime)
+      })
+      .collect();
+    let select_menu = CreateSelectMenu::new(CustomId::SelectLifetime.session(session), CreateSelectMenuKind::String { options })
+      .min_values(1)
+      .max_values(1);
+    let response = EditInteractionResponse::new()
+      .embed(embed)
+      .components(vec![
+        CreateActionRow::SelectMenu(select_menu),
+        navigation(session, Some(CustomId::BackToMember), locale),
+      ]);
+    self.edit_question(http, session, response).await
+  }
+  // 期限の選択を飛ばす「前回と同じ設定で作成」とプリセットで、保存していた期限をサーバーの上限に合わせる
+  pub async fn clamp_lifetime(&self, data: &mut WebhookData) -> Result<(), BotError> {
+    let settings = self.redis_client.get_lifetime_settings(self.redis_client.guild).await?;
+    data.lifetime = settings.clamp(data.lifetime);
+    Ok(())
+  }
+}
+
+// "21:00" のような開始時刻を、timezone で次にその時刻になる日時に変換する
+pub fn start_time(input: &str, timezone: Tz, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+  let input = input.trim().replace('：', ":");
+  let time = NaiveTime::parse_from_str(&input, "%H:%M").ok()?;
+  let today = now.with_timezone(&timezone).date_naive();
+  let start = timezone.from_local_datetime(&today.and_time(time)).earliest()?.with_timezone(&Utc);
+  if start > now {
+    Some(start)
+  } else {
+    Some(start + Duration::days(1))
+  }
+}
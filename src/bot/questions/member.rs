@@ -1,45 +1,35 @@
-use serenity::all::{CacheHttp, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http, UserId};
+use serenity::all::{CacheHttp, CreateActionRow, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http};
 
-use crate::{bot::{colors::BASE_COLOR, types::{Member, Mode, WebhookDataExt}, Handler}, error::BotError};
+use crate::{bot::{colors::BASE_COLOR, custom_id::{CustomId, SessionId}, locale::{Locale, Text}, questions::navigation, types::{Member, Mode, WebhookDataExt}, Handler}, error::BotError};
 
 impl Handler {
-  pub async fn member<T>(&self, http: T, user: UserId, mode: &str) -> Result<(), BotError>
+  pub async fn member<T>(&self, http: T, session: SessionId, mode: Mode, selected: Option<Member>, locale: Locale) -> Result<(), BotError>
   where 
     T: AsRef<Http> + CacheHttp + Copy,
   {
     let embed = CreateEmbed::new()
-      .title("人数を選択してください")
+      .title(Text::SelectMember.get(locale))
       .color(BASE_COLOR);
-    let mut select_menu_options = vec![
-      CreateSelectMenuOption::new(Member::Duo.as_str(), Member::Duo.as_str()),
-      CreateSelectMenuOption::new(Member::Trio.as_str(), Member::Trio.as_str()),
-      CreateSelectMenuOption::new(Member::FullParty.as_str(), Member::FullParty.as_str()),
-    ];
-    if mode != Mode::Competitive.as_str() {
-      select_menu_options.insert(2, CreateSelectMenuOption::new(Member::Quad.as_str(), Member::Quad.as_str()));
-    }
-    if mode == Mode::Custom.as_str() {
-      select_menu_options.push(CreateSelectMenuOption::new(Member::Six.as_str(), Member::Six.as_str()));
-      select_menu_options.push(CreateSelectMenuOption::new(Member::Seven.as_str(), Member::Seven.as_str()));
-      select_menu_options.push(CreateSelectMenuOption::new(Member::Eight.as_str(), Member::Eight.as_str()));
-      select_menu_options.push(CreateSelectMenuOption::new(Member::Nine.as_str(), Member::Nine.as_str()));
-      select_menu_options.push(CreateSelectMenuOption::new(Member::Ten.as_str(), Member::Ten.as_str()));
-    }
-    let select_menu = CreateSelectMenu::new("人数選択", CreateSelectMenuKind::String {
+    let select_menu_options = Member::variants()
+      .filter(|member| member.is_available(mode))
+      .map(|member| {
+        CreateSelectMenuOption::new(member.label(locale), member.as_str())
+          .default_selection(selected == Some(member))
+      })
+      .collect();
+    let select_menu = CreateSelectMenu::new(CustomId::SelectMember.session(session), CreateSelectMenuKind::String {
       options: select_menu_options
     })
     .min_values(1)
     .max_values(1);
+    // コンペティティブの場合はランク選択、それ以外はモード選択に戻る
+    let back = if mode == Mode::Competitive { CustomId::BackToRank } else { CustomId::BackToMode };
     let response = EditInteractionResponse::new()
       .embed(embed)
-      .select_menu(select_menu);
-    let component_store = self.component_store.lock().await;
-    if let Some(comp) = component_store.get(&user) {
-      comp.edit_response(http, response).await?;
-      drop(component_store);
-      return Ok(());
-    } else {
-      return Err(BotError::ComponentInteractionNotFound);
-    }
+      .components(vec![
+        CreateActionRow::SelectMenu(select_menu),
+        navigation(session, Some(back), locale),
+      ]);
+    self.edit_question(http, session, response).await
   }
-}
\ No newline at end of file
+}
@@ -1,27 +1,45 @@
 use serenity::all::{CacheHttp, ComponentInteraction, CreateActionRow, CreateInputText, CreateInteractionResponse, CreateModal, Http, InputTextStyle};
 
-use crate::{bot::Handler, error::BotError};
+use crate::{bot::{custom_id::{CustomId, SessionId}, locale::{Locale, Text}, types::Lifetime, Handler}, error::BotError};
 
 impl Handler {
-  pub async fn message<T>(&self, http: T, comp: &ComponentInteraction) -> Result<(), BotError> 
+  pub async fn message<T>(&self, http: T, comp: &ComponentInteraction, session: SessionId, default: Option<&str>, locale: Locale) -> Result<(), BotError> 
   where 
     T: AsRef<Http> + CacheHttp + Copy,
   {
-    let action_row = vec![
-      CreateActionRow::InputText(
-        CreateInputText::new(
-          InputTextStyle::Short,
-          "募集メッセージを入力しましょう",
-          "募集メッセージ"
-        )
-        .required(false)
-        .max_length(100)
-        .placeholder("例: たくさん喋れる人募集！")
-      )
-    ];
-    let modal = CreateModal::new("募集メッセージ", "募集メッセージ").components(action_row);
-    let response = CreateInteractionResponse::Modal(modal);
+    // 開始時刻までの募集では開始時刻も入力する
+    let start = self.get_question_state(session).await?.lifetime == Some(Lifetime::UntilStart);
+    let response = CreateInteractionResponse::Modal(message_modal(session, default, start, locale));
     comp.create_response(http, response).await?;
     Ok(())
   }
 }
+
+// default: プリセットに保存された募集メッセージ
+// start: 開始時刻の入力欄を表示する
+pub fn message_modal(session: SessionId, default: Option<&str>, start: bool, locale: Locale) -> CreateModal {
+  let mut input = CreateInputText::new(
+    InputTextStyle::Short,
+    Text::MessageInputLabel.get(locale),
+    CustomId::MessageInput
+  )
+  .required(false)
+  .max_length(100)
+  .placeholder(Text::MessageInputPlaceholder.get(locale));
+  if let Some(value) = default {
+    input = input.value(value);
+  }
+  let mut components = vec![CreateActionRow::InputText(input)];
+  if start {
+    let start_input = CreateInputText::new(
+      InputTextStyle::Short,
+      Text::StartInputLabel.get(locale),
+      CustomId::StartInput
+    )
+    .required(true)
+    .max_length(5)
+    .placeholder("21:00");
+    components.push(CreateActionRow::InputText(start_input));
+  }
+  CreateModal::new(CustomId::MessageModal.session(session), Text::MessageModalTitle.get(locale)).components(components)
+}
@@ -1,34 +1,30 @@
-use serenity::all::{CacheHttp, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http, UserId};
+use serenity::all::{CacheHttp, CreateActionRow, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http};
 
-use crate::{bot::{colors::BASE_COLOR, types::{Mode, WebhookDataExt}, Handler}, error::BotError};
+use crate::{bot::{colors::BASE_COLOR, custom_id::{CustomId, SessionId}, locale::{Locale, Text}, questions::navigation, types::{Mode, WebhookDataExt}, Handler}, error::BotError};
 
 impl Handler {
-  pub async fn mode<T>(&self, http: T, user: UserId) -> Result<(), BotError>
+  pub async fn mode<T>(&self, http: T, session: SessionId, selected: Option<Mode>, locale: Locale) -> Result<(), BotError>
   where
     T: AsRef<Http> + CacheHttp + Copy,
   {
     let embed = CreateEmbed::new()
-      .title("モードを選択してください")
+      .title(Text::SelectMode.get(locale))
       .color(BASE_COLOR);
-    let select_menu = CreateSelectMenu::new("モード選択", CreateSelectMenuKind::String {
-      options: vec![
-        CreateSelectMenuOption::new(Mode::Unrated.as_str(), Mode::Unrated.as_str()),
-        CreateSelectMenuOption::new(Mode::Competitive.as_str(), Mode::Competitive.as_str()),
-        CreateSelectMenuOption::new(Mode::Custom.as_str(), Mode::Custom.as_str()),
-      ]
-    })
-    .min_values(1)
-    .max_values(1);
+    let options = Mode::variants()
+      .map(|mode| {
+        CreateSelectMenuOption::new(mode.label(locale), mode.as_str())
+          .default_selection(selected == Some(mode))
+      })
+      .collect();
+    let select_menu = CreateSelectMenu::new(CustomId::SelectMode.session(session), CreateSelectMenuKind::String { options })
+      .min_values(1)
+      .max_values(1);
     let response = EditInteractionResponse::new()
       .embed(embed)
-      .select_menu(select_menu);
-    let component_store = self.component_store.lock().await;
-    if let Some(comp) = component_store.get(&user) {
-      comp.edit_response(http, response).await?;
-      drop(component_store);
-      Ok(())
-    } else {
-      Err(BotError::ComponentInteractionNotFound)
-    }
+      .components(vec![
+        CreateActionRow::SelectMenu(select_menu),
+        navigation(session, Some(CustomId::BackToServer), locale),
+      ]);
+    self.edit_question(http, session, response).await
   }
-}
\ No newline at end of file
+}
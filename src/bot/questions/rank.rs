@@ -1,41 +1,30 @@
-use serenity::all::{CacheHttp, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http, UserId};
+use serenity::all::{CacheHttp, CreateActionRow, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http};
 
-use crate::{bot::{colors::BASE_COLOR, types::{Rank, WebhookDataExt}, Handler}, error::BotError};
+use crate::{bot::{colors::BASE_COLOR, custom_id::{CustomId, SessionId}, locale::{Locale, Text}, questions::navigation, types::{Rank, WebhookDataExt}, Handler}, error::BotError};
 
 impl Handler {
-  pub async fn rank<T>(&self, http: T, user: UserId) -> Result<(), BotError> 
+  pub async fn rank<T>(&self, http: T, session: SessionId, selected: Option<Rank>, locale: Locale) -> Result<(), BotError> 
   where
     T: AsRef<Http> + CacheHttp + Copy,
   {
     let embed = CreateEmbed::new()
-      .title("ランクを選択してください")
+      .title(Text::SelectRank.get(locale))
       .color(BASE_COLOR);
-    let select_menu = CreateSelectMenu::new("ランク選択", CreateSelectMenuKind::String {
-      options: vec![
-        CreateSelectMenuOption::new(Rank::Unranked.as_str(), Rank::Unranked.as_str()),
-        CreateSelectMenuOption::new(Rank::Iron.as_str(), Rank::Iron.as_str()),
-        CreateSelectMenuOption::new(Rank::Bronze.as_str(), Rank::Bronze.as_str()),
-        CreateSelectMenuOption::new(Rank::Silver.as_str(), Rank::Silver.as_str()),
-        CreateSelectMenuOption::new(Rank::Gold.as_str(), Rank::Gold.as_str()),
-        CreateSelectMenuOption::new(Rank::Platinum.as_str(), Rank::Platinum.as_str()),
-        CreateSelectMenuOption::new(Rank::Diamond.as_str(), Rank::Diamond.as_str()),
-        CreateSelectMenuOption::new(Rank::Ascendant.as_str(), Rank::Ascendant.as_str()),
-        CreateSelectMenuOption::new(Rank::Immortal.as_str(), Rank::Immortal.as_str()),
-        CreateSelectMenuOption::new(Rank::Radiant.as_str(), Rank::Radiant.as_str()),
-      ]
-    })
-    .min_values(1)
-    .max_values(1);
+    let options = Rank::variants()
+      .map(|rank| {
+        CreateSelectMenuOption::new(rank.label(locale), rank.as_str())
+          .default_selection(selected == Some(rank))
+      })
+      .collect();
+    let select_menu = CreateSelectMenu::new(CustomId::SelectRank.session(session), CreateSelectMenuKind::String { options })
+      .min_values(1)
+      .max_values(1);
     let response = EditInteractionResponse::new()
       .embed(embed)
-      .select_menu(select_menu);
-    let component_store = self.component_store.lock().await;
-    if let Some(comp) = component_store.get(&user) {
-      comp.edit_response(http, response).await?;
-      drop(component_store);
-      return Ok(());
-    } else {
-      return Err(BotError::ComponentInteractionNotFound);
-    }
+      .components(vec![
+        CreateActionRow::SelectMenu(select_menu),
+        navigation(session, Some(CustomId::BackToMode), locale),
+      ]);
+    self.edit_question(http, session, response).await
   }
-}
\ No newline at end of file
+}
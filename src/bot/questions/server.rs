@@ -1,36 +1,88 @@
-use serenity::all::{CacheHttp, ComponentInteraction, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, UserId};
+use serenity::all::{ButtonStyle, CacheHttp, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, Http, ReactionType, UserId};
 
-use crate::{bot::{colors::BASE_COLOR, types::{ApServer, WebhookDataExt}, Handler}, error::BotError};
+use crate::{bot::{colors::BASE_COLOR, custom_id::{CustomId, SessionId}, locale::{Locale, Text}, questions::navigation, types::{ApServer, WebhookData, WebhookDataExt}, Handler}, error::BotError};
 
 impl Handler {
-  pub async fn server<T>(&self, user: UserId, http: T, comp: &ComponentInteraction) -> Result<(), BotError> 
+  pub async fn server<T>(&self, session: SessionId, http: T, comp: &ComponentInteraction, locale: Locale) -> Result<(), BotError> 
   where
     T: CacheHttp + Send + Sync,
   {
+    let user = comp.user.id;
     let mut component_store = self.component_store.lock().await;
-    component_store.insert(user, comp.clone());
+    component_store.insert(session, comp.clone());
     drop(component_store);
-    let embed = CreateEmbed::new()
-      .title("サーバーを選択してください")
-      .color(BASE_COLOR);
-    let select_menu = CreateSelectMenu::new("サーバー選択", CreateSelectMenuKind::String {
-      options: vec![
-        CreateSelectMenuOption::new(ApServer::Tokyo.as_str(), ApServer::Tokyo.as_str()),
-        CreateSelectMenuOption::new(ApServer::HongKong.as_str(), ApServer::HongKong.as_str()),
-        CreateSelectMenuOption::new(ApServer::Singapore.as_str(), ApServer::Singapore.as_str()),
-        CreateSelectMenuOption::new(ApServer::Sydney.as_str(), ApServer::Sydney.as_str()),
-        CreateSelectMenuOption::new(ApServer::Mumbai.as_str(), ApServer::Mumbai.as_str()),
-      ]
-    })
-    .min_values(1)
-    .max_values(1);
+    let (last_settings, presets) = self.shortcuts(user).await;
+    let (embed, components) = server_question(session, None, last_settings.as_ref(), &presets, locale);
     let response = CreateInteractionResponse::Message(
       CreateInteractionResponseMessage::new()
         .embed(embed)
-        .select_menu(select_menu)
+        .components(components)
         .ephemeral(true)
     );
     comp.create_response(http, response).await?;
     Ok(())
   }
-}
\ No newline at end of file
+  // モード選択から戻った時に、選択済みのサーバーを既定値にして再表示する
+  pub async fn back_to_server<T>(&self, http: T, session: SessionId, user: UserId, selected: ApServer, locale: Locale) -> Result<(), BotError>
+  where
+    T: AsRef<Http> + CacheHttp + Copy,
+  {
+    let (last_settings, presets) = self.shortcuts(user).await;
+    let (embed, components) = server_question(session, Some(selected), last_settings.as_ref(), &presets, locale);
+    let response = EditInteractionResponse::new()
+      .embed(embed)
+      .components(components);
+    self.edit_question(http, session, response).await
+  }
+  // 「前回と同じ設定で作成」とプリセットの選択肢。どちらも省略できるため、読み出せなくても作成は続ける
+  async fn shortcuts(&self, user: UserId) -> (Option<WebhookData>, Vec<String>) {
+    let last_settings = self.redis_client.get_last_settings(user).await.unwrap_or_else(|e| {
+      e.log("Failed to load last settings");
+      None
+    });
+    let presets = self.redis_client.get_preset_names(user).await.unwrap_or_else(|e| {
+      e.log("Failed to load preset names");
+      Vec::new()
+    });
+    (last_settings, presets)
+  }
+}
+
+fn server_question(session: SessionId, selected: Option<ApServer>, last_settings: Option<&WebhookData>, presets: &[String], locale: Locale) -> (CreateEmbed, Vec<CreateActionRow>) {
+  let mut embed = CreateEmbed::new()
+    .title(Text::SelectServer.get(locale))
+    .color(BASE_COLOR);
+  let options = ApServer::variants()
+    .map(|server| {
+      CreateSelectMenuOption::new(server.label(locale), server.as_str())
+        .default_selection(selected == Some(server))
+    })
+    .collect();
+  let select_menu = CreateSelectMenu::new(CustomId::SelectServer.session(session), CreateSelectMenuKind::String { options })
+    .min_values(1)
+    .max_values(1);
+  let mut components = vec![CreateActionRow::SelectMenu(select_menu)];
+  if let Some(data) = last_settings {
+    embed = embed.description(Text::LastSettings.format(locale, &[&data.summary(locale)]));
+    components.push(CreateActionRow::Buttons(vec![
+      CreateButton::new(CustomId::LastSettings.session(session))
+        .label(Text::LastSettingsButton.get(locale))
+        .style(ButtonStyle::Primary)
+        .emoji(ReactionType::Unicode("🔁".to_string()))
+    ]));
+  }
+  if !presets.is_empty() {
+    // セレクトメニューの選択肢は25個まで
+    let options = presets.iter()
+      .take(25)
+      .map(|name| CreateSelectMenuOption::new(name, name))
+      .collect();
+    let preset_menu = CreateSelectMenu::new(CustomId::SelectPreset.session(session), CreateSelectMenuKind::String { options })
+      .placeholder(Text::PresetPlaceholder.get(locale))
+      .min_values(1)
+      .max_values(1);
+    components.push(CreateActionRow::SelectMenu(preset_menu));
+  }
+  components.push(navigation(session, None, locale));
+  (embed, components)
+}
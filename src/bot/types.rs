@@ -1,19 +1,28 @@
 use redis::{aio::ConnectionManager, AsyncTypedCommands, Client};
-use serenity::all::{Builder, CacheHttp, ChannelId, CreateWebhook, Http, MessageId, UserId, Webhook};
-use tokio::sync::Mutex;
-use std::{str::FromStr, sync::Arc};
-use crate::{bot::colors::*, config, error::BotError};
+use serenity::all::{Builder, CacheHttp, ChannelId, CreateWebhook, GuildId, Http, MessageId, Timestamp, UserId, Webhook};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+use crate::{bot::{colors::*, custom_id::SessionId, locale::Locale, migration}, config::Config, error::BotError, metrics::{time_discord, TimedConnection}};
 
-const THREE_DAYS_SECONDS: i64 = 3 * 24 * 60 * 60;
+// 質問フローのインタラクションのトークンの有効期限 (15分)
+pub const QUESTION_STATE_EXPIRY_SECONDS: i64 = 15 * 60;
+// 締め切った募集の結果を残す件数
+const HISTORY_LIMIT: isize = 1000;
 
 #[derive(Clone)]
 pub struct RedisClient {
-  pub connection: Arc<Mutex<ConnectionManager>>,
+  // ConnectionManagerは多重化された接続を共有しているため、クローンして並行に使う
+  pub connection: TimedConnection,
+  pub expiry_seconds: i64,
+  pub key_prefix: String,
+  // CHANNEL_IDのチャンネルが属するサーバー
+  pub guild: GuildId,
 }
 
 pub trait WebhookDataExt: Sized {
   fn variants() -> impl Iterator<Item = Self>;
+  // Redisへの保存、セレクトメニューの値に使う識別子。表示文言は label で取得する
   fn as_str(&self) -> &'static str;
+  fn label(&self, locale: Locale) -> &'static str;
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +33,19 @@ pub struct WebhookData {
   pub rank: Option<Rank>,
   pub member: Member,
   pub joined: Vec<UserId>,
+  // 作成フローで選んだ募集の期限。Noneの場合はサーバーの既定値
+  pub lifetime: Option<Lifetime>,
+  // 募集の締め切り (UNIX時間)。投稿時に決まる
+  pub expires_at: Option<i64>,
+  // 作成者が締め切った時刻 (UNIX時間)。締め切った募集はボタンを無効にして残す
+  pub closed_at: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Preset {
+  pub name: String,
+  pub data: WebhookData,
+  pub message: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -56,6 +78,39 @@ pub enum Rank {
   Radiant,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifetime {
+  ThirtyMinutes,
+  OneHour,
+  ThreeHours,
+  OneDay,
+  // 募集メッセージと一緒に入力した開始時刻まで
+  UntilStart,
+}
+
+// サーバーごとの募集の期限の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LifetimeSettings {
+  pub default: Lifetime,
+  // サーバーで設定した上限。Noneの場合は EXPIRY_SECONDS のみで制限する
+  pub max: Option<Lifetime>,
+  // 上限 (秒)。EXPIRY_SECONDS を超えない
+  pub max_seconds: i64,
+}
+
+impl LifetimeSettings {
+  // 前回の設定やプリセットに保存した期限が上限を超える場合は、上限以下で最も長い期限にする
+  // 開始時刻までの場合は入力された時刻で確認する
+  pub fn clamp(&self, lifetime: Option<Lifetime>) -> Option<Lifetime> {
+    match lifetime?.seconds() {
+      Some(seconds) if seconds > self.max_seconds => Lifetime::variants()
+        .filter(|l| l.seconds().is_some_and(|s| s <= self.max_seconds))
+        .last(),
+      _ => lifetime,
+    }
+  }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Member {
@@ -79,24 +134,148 @@ impl WebhookData {
       rank: None,
       member: Member::Duo,
       joined: vec![id],
+      lifetime: None,
+      expires_at: None,
+      closed_at: None,
+    }
+  }
+  pub fn slots_left(&self) -> usize {
+    (u8::from(self.member) as usize).saturating_sub(self.joined.len())
+  }
+  pub fn summary(&self, locale: Locale) -> String {
+    format!(
+      "{} / {}{} / {}",
+      self.server.label(locale),
+      self.mode.label(locale),
+      self.rank.map_or(String::new(), |r| format!(" / {}", r.label(locale))),
+      self.member.label(locale),
+    )
+  }
+}
+
+// /list の絞り込み条件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecruitmentFilter {
+  pub server: Option<ApServer>,
+  pub mode: Option<Mode>,
+  pub rank: Option<Rank>,
+  // 空きのある募集だけにする
+  pub has_slots: bool,
+}
+
+// ページ送りのボタンのcustom_idに含める形式 ({server},{mode},{rank},{has_slots})。未指定は "-"
+impl Display for RecruitmentFilter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{},{},{},{}",
+      self.server.map_or("-", |s| s.as_str()),
+      self.mode.map_or("-", |m| m.as_str()),
+      self.rank.map_or("-", |r| r.as_str()),
+      u8::from(self.has_slots),
+    )
+  }
+}
+
+impl FromStr for RecruitmentFilter {
+  type Err = &'static str;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    fn field<T: FromStr>(value: &str) -> Result<Option<T>, &'static str> {
+      match value {
+        "-" => Ok(None),
+        value => T::from_str(value).map(Some).map_err(|_| "Invalid filter"),
+      }
     }
-  } 
+    let [server, mode, rank, has_slots] = s.split(',').collect::<Vec<&str>>()[..] else {
+      return Err("Invalid filter");
+    };
+    Ok(Self {
+      server: field(server)?,
+      mode: field(mode)?,
+      rank: field(rank)?,
+      has_slots: has_slots == "1",
+    })
+  }
+}
+
+// キーのレイアウト。マイグレーションのテストでRedisに接続せずに組み立てられるよう、RedisClientから分けている
+// {prefix}:{name}                    スキーマバージョン等、Bot全体で1つの値
+// {prefix}:{guild}:{name}            Webhook URL、最新の募集作成パネル、表示言語、募集の期限の設定
+// {prefix}:{guild}:recruit:{message} 募集
+// {prefix}:{guild}:open              募集中の募集のメッセージID (スコアは作成時刻)
+// {prefix}:{guild}:open:{field}:{value} 絞り込み用のセット (server, mode, rank, has_slots)
+// {prefix}:{guild}:open:expires      募集中の募集のメッセージID (スコアは締め切り)
+// {prefix}:{guild}:history           締め切った募集の結果 (新しい順のJSON)
+// {prefix}:user:{user}:{name}        前回の設定、プリセット
+#[derive(Clone, Copy)]
+pub struct KeyLayout<'a> {
+  pub prefix: &'a str,
+  // CHANNEL_IDのチャンネルが属するサーバー
+  pub guild: GuildId,
+}
+
+impl KeyLayout<'_> {
+  pub fn global_key(&self, name: &str) -> String {
+    format!("{}:{}", self.prefix, name)
+  }
+  pub fn guild_key(&self, guild: GuildId, name: &str) -> String {
+    format!("{}:{}:{}", self.prefix, guild.get(), name)
+  }
+  pub fn recruit_key(&self, message: MessageId) -> String {
+    format!("{}:{}:recruit:{}", self.prefix, self.guild.get(), message.get())
+  }
+  pub fn user_key(&self, user: UserId, name: impl Display) -> String {
+    format!("{}:user:{}:{}", self.prefix, user.get(), name)
+  }
 }
 
 impl RedisClient {
-  pub async fn new(redis_pass: &str) -> Result<Self, BotError> {
-    let client = Client::open(format!("redis://:{}@127.0.0.1/", redis_pass))?;
-    let conn = ConnectionManager::new(client).await?;
+  pub async fn new(config: &Config, guild: GuildId) -> Result<Self, BotError> {
+    let client = Client::open(config.redis_url.as_str())
+      .map_err(|e| BotError::InvalidConfig(format!("REDIS_URL の値が不正です: {}", e)))?;
+    // パスワードを含めないよう、接続先はホストとポートのみ表示する
+    let info = client.get_connection_info();
+    let target = format!("{} (db {})", info.addr, info.redis.db);
+    let conn = ConnectionManager::new(client).await
+      .map_err(|e| BotError::RedisConnectError(target.clone(), e))?;
+    tracing::info!(target = %target, "Connected to Redis");
     Ok(Self {
-      connection: Arc::new(Mutex::new(conn)),
+      connection: TimedConnection(conn),
+      expiry_seconds: config.expiry_seconds,
+      key_prefix: config.redis_key_prefix.clone(),
+      guild,
     })
   }
+  pub fn layout(&self) -> KeyLayout<'_> {
+    KeyLayout { prefix: &self.key_prefix, guild: self.guild }
+  }
+  pub fn global_key(&self, name: &str) -> String {
+    self.layout().global_key(name)
+  }
+  pub fn guild_key(&self, guild: GuildId, name: &str) -> String {
+    self.layout().guild_key(guild, name)
+  }
+  pub fn recruit_key(&self, message: MessageId) -> String {
+    self.layout().recruit_key(message)
+  }
+  pub fn open_key(&self) -> String {
+    self.guild_key(self.guild, "open")
+  }
+  pub fn user_key(&self, user: UserId, name: impl Display) -> String {
+    self.layout().user_key(user, name)
+  }
+  // 全ユーザーの同じ名前のキーにマッチするSCANのパターン
+  pub fn user_key_pattern(&self, name: &str) -> String {
+    format!("{}:user:*:{}", self.key_prefix, name)
+  }
   pub async fn store_webhook_data(&self, id: MessageId, data: &WebhookData) -> Result<(), BotError> {
+    let version = migration::SCHEMA_VERSION.to_string();
     let creator = data.creator.get().to_string();
     let joined_user: String = data.joined.iter()
       .map(|u| format!("{}", u.get()))
       .collect::<Vec<String>>()
       .join(",");
+    let expires_at = data.expires_at.map_or(String::new(), |t| t.to_string());
     let fields_value = [
       ("creator", creator.as_str()),
       ("server", data.server.as_str()),
@@ -104,17 +283,159 @@ impl RedisClient {
       ("rank", data.rank.map_or("None", |r| r.as_str())),
       ("member", data.member.as_str()),
       ("joined", joined_user.as_str()),
+      ("lifetime", data.lifetime.map_or("", |l| l.as_str())),
+      ("expires_at", expires_at.as_str()),
+      ("version", version.as_str()),
+    ];
+    let mut conn = self.connection.clone();
+    conn.hset_multiple(self.recruit_key(id), &fields_value).await?;
+    match data.expires_at {
+      Some(expires_at) => conn.expire_at(self.recruit_key(id), expires_at).await?,
+      None => conn.expire(self.recruit_key(id), self.expiry_seconds).await?,
+    };
+    self.add_to_index(id, data).await
+  }
+  pub async fn delete_webhook_data(&self, id: MessageId) -> Result<(), BotError> {
+    let mut conn = self.connection.clone();
+    conn.del(self.recruit_key(id)).await?;
+    self.remove_from_index(id).await
+  }
+  // 締め切った募集の結果を記録する。古いものから HISTORY_LIMIT 件を超えた分を捨てる
+  pub async fn record_outcome(&self, id: MessageId, data: &WebhookData) -> Result<(), BotError> {
+    let outcome = serde_json::json!({
+      "message": id.get().to_string(),
+      "creator": data.creator.get().to_string(),
+      "server": data.server.as_str(),
+      "mode": data.mode.as_str(),
+      "rank": data.rank.map(|r| r.as_str()),
+      "member": u8::from(data.member),
+      "joined": data.joined.iter().map(|u| u.get().to_string()).collect::<Vec<String>>(),
+      "filled": data.slots_left() == 0,
+      "created_at": id.created_at().unix_timestamp(),
+      "closed_at": data.closed_at,
+    });
+    let key = self.guild_key(self.guild, "history");
+    let mut pipe = redis::pipe();
+    pipe.lpush(&key, outcome.to_string()).ignore();
+    pipe.ltrim(&key, 0, HISTORY_LIMIT - 1).ignore();
+    let mut conn = self.connection.clone();
+    pipe.query_async::<()>(&mut conn).await?;
+    Ok(())
+  }
+  // 募集の検索用の索引に追加する
+  pub async fn add_to_index(&self, id: MessageId, data: &WebhookData) -> Result<(), BotError> {
+    let mut pipe = redis::pipe();
+    pipe.zadd(self.open_key(), id.get(), id.created_at().unix_timestamp()).ignore();
+    if let Some(expires_at) = data.expires_at {
+      pipe.zadd(self.expires_key(), id.get(), expires_at).ignore();
+    }
+    for key in self.index_keys(data) {
+      pipe.sadd(key, id.get()).ignore();
+    }
+    let mut conn = self.connection.clone();
+    pipe.query_async::<()>(&mut conn).await?;
+    Ok(())
+  }
+  // 削除時には募集の内容が残っていない場合があるため、すべての索引から取り除く
+  pub async fn remove_from_index(&self, id: MessageId) -> Result<(), BotError> {
+    let mut pipe = redis::pipe();
+    pipe.zrem(self.open_key(), id.get()).ignore();
+    pipe.zrem(self.expires_key(), id.get()).ignore();
+    for key in self.all_index_keys() {
+      pipe.srem(key, id.get()).ignore();
+    }
+    let mut conn = self.connection.clone();
+    pipe.query_async::<()>(&mut conn).await?;
+    Ok(())
+  }
+  // 募集が属する絞り込み用のセット
+  fn index_keys(&self, data: &WebhookData) -> Vec<String> {
+    let mut keys = vec![
+      self.index_key("server", data.server.as_str()),
+      self.index_key("mode", data.mode.as_str()),
     ];
-    let mut conn = self.connection.lock().await;
-    conn.hset_multiple(id.get(), &fields_value).await?;
-    conn.expire(id.get(), THREE_DAYS_SECONDS).await?;
-    drop(conn);
+    if let Some(rank) = data.rank {
+      keys.push(self.index_key("rank", rank.as_str()));
+    }
+    if data.slots_left() > 0 {
+      keys.push(self.has_slots_key());
+    }
+    keys
+  }
+  fn all_index_keys(&self) -> Vec<String> {
+    ApServer::variants().map(|s| self.index_key("server", s.as_str()))
+      .chain(Mode::variants().map(|m| self.index_key("mode", m.as_str())))
+      .chain(Rank::variants().map(|r| self.index_key("rank", r.as_str())))
+      .chain([self.has_slots_key()])
+      .collect()
+  }
+  fn index_key(&self, field: &str, value: &str) -> String {
+    self.guild_key(self.guild, &format!("open:{}:{}", field, value))
+  }
+  // 募集中の募集のメッセージID (スコアは締め切り)
+  fn expires_key(&self) -> String {
+    self.guild_key(self.guild, "open:expires")
+  }
+  // 参加ボタンの処理で空きの有無が変わったときに更新する
+  pub fn has_slots_key(&self) -> String {
+    self.guild_key(self.guild, "open:has_slots")
+  }
+  // 有効期限を過ぎた募集を索引から取り除く
+  async fn prune_index(&self) -> Result<(), BotError> {
+    let mut conn = self.connection.clone();
+    let now = Timestamp::now().unix_timestamp();
+    let mut expired = conn.zrangebyscore(self.expires_key(), "-inf", now).await?;
+    // 締め切りを保存する前に作成された募集は EXPIRY_SECONDS で期限切れになる
+    expired.extend(conn.zrangebyscore(self.open_key(), "-inf", format!("({}", now - self.expiry_seconds)).await?);
+    for id in expired.iter().filter_map(|id| MessageId::from_str(id).ok()) {
+      self.remove_from_index(id).await?;
+    }
     Ok(())
   }
+  // 条件に合う募集中の募集のメッセージIDを新しい順に返す
+  pub async fn find_recruitments(&self, filter: &RecruitmentFilter) -> Result<Vec<MessageId>, BotError> {
+    self.prune_index().await?;
+    let mut keys = Vec::new();
+    if let Some(server) = filter.server {
+      keys.push(self.index_key("server", server.as_str()));
+    }
+    if let Some(mode) = filter.mode {
+      keys.push(self.index_key("mode", mode.as_str()));
+    }
+    if let Some(rank) = filter.rank {
+      keys.push(self.index_key("rank", rank.as_str()));
+    }
+    if filter.has_slots {
+      keys.push(self.has_slots_key());
+    }
+    let mut conn = self.connection.clone();
+    let ids: Vec<String> = if keys.is_empty() {
+      conn.zrange(self.open_key(), 0, -1).await?
+    } else {
+      conn.sinter(&keys).await?.into_iter().collect()
+    };
+    let mut ids: Vec<MessageId> = ids.iter().filter_map(|id| MessageId::from_str(id).ok()).collect();
+    // メッセージIDは作成時刻順に大きくなる
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(ids)
+  }
+  // 一覧に表示する募集を読み出す。有効期限等で消えていた募集は索引から取り除いて飛ばす
+  pub async fn get_recruitments(&self, ids: &[MessageId]) -> Result<Vec<(MessageId, WebhookData)>, BotError> {
+    let mut recruitments = Vec::new();
+    for &id in ids {
+      match self.get_webhook_data(id).await {
+        Ok(data) => recruitments.push((id, data)),
+        Err(BotError::WebhookDataNotFound) => self.remove_from_index(id).await?,
+        Err(e) => return Err(e),
+      }
+    }
+    Ok(recruitments)
+  }
   pub async fn get_webhook_data(&self, id: MessageId) -> Result<WebhookData, BotError> {
-    let mut conn = self.connection.lock().await;
-    let hash_set = conn.hgetall(id.get()).await?;
-    drop(conn);
+    let mut conn = self.connection.clone();
+    let mut hash_set = conn.hgetall(self.recruit_key(id)).await?;
+    // マイグレーション前のデータを読んだ場合に備えて変換しておく
+    migration::upgrade_record(&mut hash_set);
     let creator = UserId::from_str(hash_set.get("creator").ok_or(BotError::WebhookDataNotFound)?)
       .map_err(|_| BotError::WebhookDataNotFound)?;
     let server = ApServer::from_str(hash_set.get("server").ok_or(BotError::WebhookDataNotFound)?)
@@ -132,6 +453,9 @@ impl RedisClient {
       .split(',')
       .filter_map(|u| UserId::from_str(u).ok())
       .collect();
+    let lifetime = hash_set.get("lifetime").and_then(|l| Lifetime::from_str(l).ok());
+    let expires_at = hash_set.get("expires_at").and_then(|t| t.parse().ok());
+    let closed_at = hash_set.get("closed_at").and_then(|t| t.parse().ok());
     let webhook_data = WebhookData {
       creator,
       server,
@@ -139,30 +463,219 @@ impl RedisClient {
       rank,
       member,
       joined,
+      lifetime,
+      expires_at,
+      closed_at,
     };
     Ok(webhook_data)
   }
-  pub async fn get_webhook<T: AsRef<Http> + CacheHttp + Copy>(&self, http: T) -> Result<Webhook, BotError> {
-    let channel = ChannelId::from_str(&config::get("CHANNEL_ID")?)?;
-    let mut conn = self.connection.lock().await;
-    let webhook_url = conn.get("webhook_url").await?;
+  // 「前回と同じ設定で作成」用に、最後に作成した募集の設定をユーザーごとに保存する
+  pub async fn store_last_settings(&self, user: UserId, data: &WebhookData) -> Result<(), BotError> {
+    let version = migration::SCHEMA_VERSION.to_string();
+    let fields_value = [
+      ("server", data.server.as_str()),
+      ("mode", data.mode.as_str()),
+      ("rank", data.rank.map_or("None", |r| r.as_str())),
+      ("member", data.member.as_str()),
+      ("lifetime", data.lifetime.map_or("", |l| l.as_str())),
+      ("version", version.as_str()),
+    ];
+    let mut conn = self.connection.clone();
+    conn.hset_multiple(self.user_key(user, "last_settings"), &fields_value).await?;
+    Ok(())
+  }
+  pub async fn get_last_settings(&self, user: UserId) -> Result<Option<WebhookData>, BotError> {
+    let mut conn = self.connection.clone();
+    let mut hash_set = conn.hgetall(self.user_key(user, "last_settings")).await?;
+    migration::upgrade_record(&mut hash_set);
+    Ok(settings_from_hash(user, &hash_set))
+  }
+  // 停止時に作成途中の質問状態を保存する。インタラクションのトークンは保存せず、フローの有効期限の間だけ保持する
+  pub async fn store_question_state(&self, session: SessionId, data: &WebhookData) -> Result<(), BotError> {
+    let version = migration::SCHEMA_VERSION.to_string();
+    let fields_value = [
+      ("server", data.server.as_str()),
+      ("mode", data.mode.as_str()),
+      ("rank", data.rank.map_or("None", |r| r.as_str())),
+      ("member", data.member.as_str()),
+      ("lifetime", data.lifetime.map_or("", |l| l.as_str())),
+      ("version", version.as_str()),
+    ];
+    let mut conn = self.connection.clone();
+    let key = self.user_key(data.creator, format!("question_state:{}", session));
+    conn.hset_multiple(&key, &fields_value).await?;
+    conn.expire_at(&key, session.started_at() + QUESTION_STATE_EXPIRY_SECONDS).await?;
+    Ok(())
+  }
+  // 保存した質問状態をすべて読み出して削除する
+  pub async fn take_question_states(&self) -> Result<Vec<(SessionId, WebhookData)>, BotError> {
+    let mut conn = self.connection.clone();
+    let mut keys = Vec::new();
+    let mut iter = conn.scan_match::<_, String>(self.user_key_pattern("question_state:*")).await?;
+    while let Some(key) = iter.next_item().await {
+      keys.push(key);
+    }
+    drop(iter);
+    let mut states = Vec::new();
+    for key in keys {
+      let hash_set = conn.hgetall(&key).await?;
+      conn.del(&key).await?;
+      // {prefix}:user:{user}:question_state:{session}
+      let parts: Vec<&str> = key.split(':').collect();
+      let user = parts.get(2).and_then(|u| UserId::from_str(u).ok());
+      let session = parts.last().and_then(|s| SessionId::from_str(s).ok());
+      let (Some(data), Some(session)) = (user.and_then(|u| settings_from_hash(u, &hash_set)), session) else {
+        continue;
+      };
+      states.push((session, data));
+    }
+    Ok(states)
+  }
+  pub async fn store_preset(&self, user: UserId, preset: &Preset) -> Result<(), BotError> {
+    let version = migration::SCHEMA_VERSION.to_string();
+    let fields_value = [
+      ("server", preset.data.server.as_str()),
+      ("mode", preset.data.mode.as_str()),
+      ("rank", preset.data.rank.map_or("None", |r| r.as_str())),
+      ("member", preset.data.member.as_str()),
+      ("lifetime", preset.data.lifetime.map_or("", |l| l.as_str())),
+      ("message", preset.message.as_deref().unwrap_or("")),
+      ("version", version.as_str()),
+    ];
+    let mut conn = self.connection.clone();
+    conn.hset_multiple(self.user_key(user, format!("preset:{}", preset.name)), &fields_value).await?;
+    conn.sadd(self.user_key(user, "presets"), &preset.name).await?;
+    Ok(())
+  }
+  pub async fn get_preset(&self, user: UserId, name: &str) -> Result<Option<Preset>, BotError> {
+    let mut conn = self.connection.clone();
+    let mut hash_set = conn.hgetall(self.user_key(user, format!("preset:{}", name))).await?;
+    migration::upgrade_record(&mut hash_set);
+    let server = hash_set.get("server").and_then(|s| ApServer::from_str(s).ok());
+    let mode = hash_set.get("mode").and_then(|m| Mode::from_str(m).ok());
+    let member = hash_set.get("member").and_then(|m| Member::from_str(m).ok());
+    let (Some(server), Some(mode), Some(member)) = (server, mode, member) else {
+      return Ok(None);
+    };
+    let rank = hash_set.get("rank")
+      .filter(|&r| r != "None")
+      .and_then(|r| Rank::from_str(r).ok());
+    let lifetime = hash_set.get("lifetime").and_then(|l| Lifetime::from_str(l).ok());
+    let message = hash_set.get("message")
+      .filter(|m| !m.is_empty())
+      .cloned();
+    Ok(Some(Preset {
+      name: name.to_string(),
+      data: WebhookData {
+        server,
+        mode,
+        rank,
+        member,
+        lifetime,
+        ..WebhookData::new(user)
+      },
+      message,
+    }))
+  }
+  pub async fn get_preset_names(&self, user: UserId) -> Result<Vec<String>, BotError> {
+    let mut conn = self.connection.clone();
+    let names = conn.smembers(self.user_key(user, "presets")).await?;
+    let mut names: Vec<String> = names.into_iter().collect();
+    names.sort();
+    Ok(names)
+  }
+  // 削除したプリセットが存在しなかった場合はfalseを返す
+  pub async fn delete_preset(&self, user: UserId, name: &str) -> Result<bool, BotError> {
+    let mut conn = self.connection.clone();
+    let removed = conn.srem(self.user_key(user, "presets"), name).await?;
+    conn.del(self.user_key(user, format!("preset:{}", name))).await?;
+    Ok(removed > 0)
+  }
+  pub async fn get_guild_locale(&self, guild: GuildId) -> Result<Option<Locale>, BotError> {
+    let mut conn = self.connection.clone();
+    let locale = conn.get(self.guild_key(guild, "locale")).await?;
+    Ok(locale.and_then(|l| Locale::from_str(&l).ok()))
+  }
+  // locale が None の場合は設定を削除し、ユーザーごとの言語に戻す
+  pub async fn set_guild_locale(&self, guild: GuildId, locale: Option<Locale>) -> Result<(), BotError> {
+    let mut conn = self.connection.clone();
+    match locale {
+      Some(locale) => conn.set(self.guild_key(guild, "locale"), locale.as_str()).await?,
+      None => {
+        conn.del(self.guild_key(guild, "locale")).await?;
+      }
+    }
+    Ok(())
+  }
+  // 未設定の項目は既定値 (1日、上限は EXPIRY_SECONDS) にする
+  pub async fn get_lifetime_settings(&self, guild: GuildId) -> Result<LifetimeSettings, BotError> {
+    let mut conn = self.connection.clone();
+    let hash_set = conn.hgetall(self.guild_key(guild, "lifetime")).await?;
+    let default = hash_set.get("default").and_then(|l| Lifetime::from_str(l).ok()).unwrap_or(Lifetime::OneDay);
+    let max = hash_set.get("max").and_then(|l| Lifetime::from_str(l).ok());
+    let max_seconds = max
+      .and_then(Lifetime::seconds)
+      .map_or(self.expiry_seconds, |s| s.min(self.expiry_seconds));
+    Ok(LifetimeSettings { default, max, max_seconds })
+  }
+  pub async fn set_lifetime_settings(&self, guild: GuildId, default: Option<Lifetime>, max: Option<Lifetime>) -> Result<(), BotError> {
+    let fields_value: Vec<(&str, &str)> = [("default", default), ("max", max)]
+      .into_iter()
+      .filter_map(|(field, lifetime)| Some((field, lifetime?.as_str())))
+      .collect();
+    if fields_value.is_empty() {
+      return Ok(());
+    }
+    let mut conn = self.connection.clone();
+    conn.hset_multiple(self.guild_key(guild, "lifetime"), &fields_value).await?;
+    Ok(())
+  }
+  pub async fn ping(&self) -> Result<(), BotError> {
+    let mut conn = self.connection.clone();
+    redis::cmd("PING").query_async::<String>(&mut conn).await?;
+    Ok(())
+  }
+  // Webhookを作成せずに、URLを保存済みか確かめる
+  pub async fn has_webhook_url(&self) -> Result<bool, BotError> {
+    let mut conn = self.connection.clone();
+    Ok(conn.exists(self.guild_key(self.guild, "webhook_url")).await?)
+  }
+  pub async fn get_webhook<T: AsRef<Http> + CacheHttp + Copy>(&self, http: T, channel: ChannelId) -> Result<Webhook, BotError> {
+    let mut conn = self.connection.clone();
+    let webhook_url = conn.get(self.guild_key(self.guild, "webhook_url")).await?;
     match webhook_url {
       Some(url) => {
-        drop(conn);
-        let webhook = Webhook::from_url(http, &url).await?;
+        let webhook = time_discord("get_webhook", Webhook::from_url(http, &url)).await?;
         Ok(webhook)
       }
       None => {
-        let webhook = CreateWebhook::new("Valo Member Bot Webhook")
-          .execute(http, channel).await?;
-        conn.set("webhook_url", webhook.url()?).await?;
-        drop(conn);
+        let webhook = time_discord("create_webhook", CreateWebhook::new("Valo Member Bot Webhook").execute(http, channel)).await?;
+        conn.set(self.guild_key(self.guild, "webhook_url"), webhook.url()?).await?;
         Ok(webhook)
       }
     }
   }
 }
 
+// 前回の設定、質問状態のハッシュから募集の設定を読み出す
+fn settings_from_hash(user: UserId, hash_set: &HashMap<String, String>) -> Option<WebhookData> {
+  let server = hash_set.get("server").and_then(|s| ApServer::from_str(s).ok())?;
+  let mode = hash_set.get("mode").and_then(|m| Mode::from_str(m).ok())?;
+  let member = hash_set.get("member").and_then(|m| Member::from_str(m).ok())?;
+  let rank = hash_set.get("rank")
+    .filter(|&r| r != "None")
+    .and_then(|r| Rank::from_str(r).ok());
+  let lifetime = hash_set.get("lifetime").and_then(|l| Lifetime::from_str(l).ok());
+  Some(WebhookData {
+    server,
+    mode,
+    rank,
+    member,
+    lifetime,
+    ..WebhookData::new(user)
+  })
+}
+
 impl WebhookDataExt for ApServer {
   fn variants() -> impl Iterator<Item = Self> {
     [
@@ -176,11 +689,29 @@ impl WebhookDataExt for ApServer {
   }
   fn as_str(&self) -> &'static str {
     match self {
-      ApServer::Tokyo => "Tokyo/東京 🇯🇵",
-      ApServer::HongKong => "Hong Kong/香港 🇭🇰",
-      ApServer::Singapore => "Singapore/シンガポール 🇸🇬",
-      ApServer::Sydney => "Sydney/シドニー 🇦🇺",
-      ApServer::Mumbai => "Mumbai/ムンバイ 🇮🇳",
+      ApServer::Tokyo => "tokyo",
+      ApServer::HongKong => "hong_kong",
+      ApServer::Singapore => "singapore",
+      ApServer::Sydney => "sydney",
+      ApServer::Mumbai => "mumbai",
+    }
+  }
+  fn label(&self, locale: Locale) -> &'static str {
+    match locale {
+      Locale::Ja => match self {
+        ApServer::Tokyo => "Tokyo/東京 🇯🇵",
+        ApServer::HongKong => "Hong Kong/香港 🇭🇰",
+        ApServer::Singapore => "Singapore/シンガポール 🇸🇬",
+        ApServer::Sydney => "Sydney/シドニー 🇦🇺",
+        ApServer::Mumbai => "Mumbai/ムンバイ 🇮🇳",
+      },
+      Locale::En => match self {
+        ApServer::Tokyo => "Tokyo 🇯🇵",
+        ApServer::HongKong => "Hong Kong 🇭🇰",
+        ApServer::Singapore => "Singapore 🇸🇬",
+        ApServer::Sydney => "Sydney 🇦🇺",
+        ApServer::Mumbai => "Mumbai 🇮🇳",
+      },
     }
   }
 }
@@ -199,9 +730,23 @@ impl WebhookDataExt for Mode {
   }
   fn as_str(&self) -> &'static str {
     match self {
-      Self::Unrated => "アンレート",
-      Self::Competitive => "コンペティティブ",
-      Self::Custom => "カスタム",
+      Self::Unrated => "unrated",
+      Self::Competitive => "competitive",
+      Self::Custom => "custom",
+    }
+  }
+  fn label(&self, locale: Locale) -> &'static str {
+    match locale {
+      Locale::Ja => match self {
+        Self::Unrated => "アンレート",
+        Self::Competitive => "コンペティティブ",
+        Self::Custom => "カスタム",
+      },
+      Locale::En => match self {
+        Self::Unrated => "Unrated",
+        Self::Competitive => "Competitive",
+        Self::Custom => "Custom",
+      },
     }
   }
 }
@@ -232,16 +777,44 @@ impl WebhookDataExt for Rank {
   }
   fn as_str(&self) -> &'static str {
     match self {
-      Self::Unranked => "どこでも",
-      Self::Iron => "アイアン",
-      Self::Bronze => "ブロンズ",
-      Self::Silver => "シルバー",
-      Self::Gold => "ゴールド",
-      Self::Platinum => "プラチナ",
-      Self::Diamond => "ダイヤモンド",
-      Self::Ascendant => "アセンダント",
-      Self::Immortal => "イモータル",
-      Self::Radiant => "レディアント",
+      Self::Unranked => "unranked",
+      Self::Iron => "iron",
+      Self::Bronze => "bronze",
+      Self::Silver => "silver",
+      Self::Gold => "gold",
+      Self::Platinum => "platinum",
+      Self::Diamond => "diamond",
+      Self::Ascendant => "ascendant",
+      Self::Immortal => "immortal",
+      Self::Radiant => "radiant",
+    }
+  }
+  fn label(&self, locale: Locale) -> &'static str {
+    match locale {
+      Locale::Ja => match self {
+        Self::Unranked => "どこでも",
+        Self::Iron => "アイアン",
+        Self::Bronze => "ブロンズ",
+        Self::Silver => "シルバー",
+        Self::Gold => "ゴールド",
+        Self::Platinum => "プラチナ",
+        Self::Diamond => "ダイヤモンド",
+        Self::Ascendant => "アセンダント",
+        Self::Immortal => "イモータル",
+        Self::Radiant => "レディアント",
+      },
+      Locale::En => match self {
+        Self::Unranked => "Any",
+        Self::Iron => "Iron",
+        Self::Bronze => "Bronze",
+        Self::Silver => "Silver",
+        Self::Gold => "Gold",
+        Self::Platinum => "Platinum",
+        Self::Diamond => "Diamond",
+        Self::Ascendant => "Ascendant",
+        Self::Immortal => "Immortal",
+        Self::Radiant => "Radiant",
+      },
     }
   }
 }
@@ -254,7 +827,7 @@ impl FromStr for Rank {
   }
 }
 impl Rank {
-  pub fn to_color(&self) -> u32 {
+  pub fn to_color(self) -> u32 {
     match self {
       Rank::Radiant => RADIANT_COLOR,
       Rank::Immortal => IMMORTAL_COLOR,
@@ -287,15 +860,41 @@ impl WebhookDataExt for Member {
   }
   fn as_str(&self) -> &'static str {
     match self {
-      Self::Duo => "デュオ",
-      Self::Trio => "トリオ",
-      Self::Quad => "クアッド",
-      Self::FullParty => "フルパ",
-      Self::Six => "6人",
-      Self::Seven => "7人",
-      Self::Eight => "8人",
-      Self::Nine => "9人",
-      Self::Ten => "10人",
+      Self::Duo => "duo",
+      Self::Trio => "trio",
+      Self::Quad => "quad",
+      Self::FullParty => "full_party",
+      Self::Six => "six",
+      Self::Seven => "seven",
+      Self::Eight => "eight",
+      Self::Nine => "nine",
+      Self::Ten => "ten",
+    }
+  }
+  fn label(&self, locale: Locale) -> &'static str {
+    match locale {
+      Locale::Ja => match self {
+        Self::Duo => "デュオ",
+        Self::Trio => "トリオ",
+        Self::Quad => "クアッド",
+        Self::FullParty => "フルパ",
+        Self::Six => "6人",
+        Self::Seven => "7人",
+        Self::Eight => "8人",
+        Self::Nine => "9人",
+        Self::Ten => "10人",
+      },
+      Locale::En => match self {
+        Self::Duo => "Duo",
+        Self::Trio => "Trio",
+        Self::Quad => "Quad",
+        Self::FullParty => "Full party",
+        Self::Six => "6 players",
+        Self::Seven => "7 players",
+        Self::Eight => "8 players",
+        Self::Nine => "9 players",
+        Self::Ten => "10 players",
+      },
     }
   }
 }
@@ -307,8 +906,92 @@ impl FromStr for Member {
       .ok_or("Invalid member size")
   }
 }
+impl Member {
+  // モードごとに選択できる人数
+  pub fn is_available(self, mode: Mode) -> bool {
+    match self {
+      Member::Quad => mode != Mode::Competitive,
+      Member::Six | Member::Seven | Member::Eight | Member::Nine | Member::Ten => mode == Mode::Custom,
+      _ => true,
+    }
+  }
+}
 impl From<Member> for u8 {
   fn from(value: Member) -> Self {
     value as u8
   }
 }
+
+impl WebhookDataExt for Lifetime {
+  fn variants() -> impl Iterator<Item = Self> {
+    [
+      Lifetime::ThirtyMinutes,
+      Lifetime::OneHour,
+      Lifetime::ThreeHours,
+      Lifetime::OneDay,
+      Lifetime::UntilStart,
+    ]
+    .into_iter()
+  }
+  fn as_str(&self) -> &'static str {
+    match self {
+      Self::ThirtyMinutes => "thirty_minutes",
+      Self::OneHour => "one_hour",
+      Self::ThreeHours => "three_hours",
+      Self::OneDay => "one_day",
+      Self::UntilStart => "until_start",
+    }
+  }
+  fn label(&self, locale: Locale) -> &'static str {
+    match locale {
+      Locale::Ja => match self {
+        Self::ThirtyMinutes => "30分",
+        Self::OneHour => "1時間",
+        Self::ThreeHours => "3時間",
+        Self::OneDay => "1日",
+        Self::UntilStart => "開始時刻まで",
+      },
+      Locale::En => match self {
+        Self::ThirtyMinutes => "30 minutes",
+        Self::OneHour => "1 hour",
+        Self::ThreeHours => "3 hours",
+        Self::OneDay => "1 day",
+        Self::UntilStart => "Until start time",
+      },
+    }
+  }
+}
+impl FromStr for Lifetime {
+  type Err = &'static str;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::variants()
+      .find(|&lifetime| lifetime.as_str() == s)
+      .ok_or("Invalid lifetime")
+  }
+}
+impl Lifetime {
+  // 開始時刻までの場合は入力された時刻で決まるためNone
+  pub fn seconds(self) -> Option<i64> {
+    match self {
+      Lifetime::ThirtyMinutes => Some(30 * 60),
+      Lifetime::OneHour => Some(60 * 60),
+      Lifetime::ThreeHours => Some(3 * 60 * 60),
+      Lifetime::OneDay => Some(24 * 60 * 60),
+      Lifetime::UntilStart => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lifetime_settings_clamp_to_max() {
+    let settings = LifetimeSettings { default: Lifetime::OneHour, max: Some(Lifetime::ThreeHours), max_seconds: 3 * 60 * 60 };
+    assert_eq!(settings.clamp(None), None);
+    assert_eq!(settings.clamp(Some(Lifetime::OneHour)), Some(Lifetime::OneHour));
+    assert_eq!(settings.clamp(Some(Lifetime::OneDay)), Some(Lifetime::ThreeHours));
+    assert_eq!(settings.clamp(Some(Lifetime::UntilStart)), Some(Lifetime::UntilStart));
+  }
+}
@@ -1,13 +1,197 @@
+use std::{fmt::Display, io::ErrorKind, net::SocketAddr, path::Path, str::FromStr};
+
+use chrono_tz::Tz;
 use dotenv::dotenv;
+use serde::Deserialize;
+use serenity::all::{ChannelId, UserId};
+use tracing_subscriber::EnvFilter;
 
 use crate::BotError;
 
-pub fn load() -> Result<(), BotError> {
-    dotenv().map_err(|e| BotError::ConfigError(e))?;
-    Ok(())
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const DEFAULT_EXPIRY_SECONDS: i64 = 3 * 24 * 60 * 60;
+const DEFAULT_REDIS_KEY_PREFIX: &str = "valo";
+const DEFAULT_LOG_FILTER: &str = "info";
+const DEFAULT_TIMEZONE: Tz = chrono_tz::Asia::Tokyo;
+const DEFAULT_HTTP_ADDR: &str = "127.0.0.1:9090";
+
+// 起動時に一度だけ読み込み、Handlerで共有する設定
+#[derive(Clone)]
+pub struct Config {
+  pub token: String,
+  pub channel_id: ChannelId,
+  pub bot_id: UserId,
+  pub redis_url: String,
+  pub redis_key_prefix: String,
+  pub base_img_url: String,
+  pub expiry_seconds: i64,
+  // EnvFilterの書式 (例: info,serenity=warn)
+  pub log_filter: String,
+  pub log_format: LogFormat,
+  // ログの時刻に使うタイムゾーン
+  pub timezone: Tz,
+  // /metrics 等を公開するアドレス
+  pub http_addr: SocketAddr,
+  // 処理中のエラーを投稿する管理者用チャンネル
+  pub admin_log_channel_id: Option<ChannelId>,
+  // マイグレーションで最初のリリース以外の形のプレフィックスなしのキーも移動する。Redisを他のアプリケーションと共有していない場合だけ有効にする
+  pub migrate_unprefixed_keys: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LogFormat {
+  Text,
+  // ログ収集基盤に送る場合
+  Json,
+}
+
+impl FromStr for LogFormat {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(LogFormat::Text),
+      "json" => Ok(LogFormat::Json),
+      _ => Err(()),
+    }
+  }
+}
+
+// CONFIG_FILE (既定: config.toml) の内容。環境変数が設定されている項目は環境変数を優先する
+#[derive(Debug, Default, Deserialize)]
+struct FileConfig {
+  token: Option<String>,
+  channel_id: Option<u64>,
+  bot_id: Option<u64>,
+  redis_url: Option<String>,
+  redis_pass: Option<String>,
+  redis_key_prefix: Option<String>,
+  base_img_url: Option<String>,
+  expiry_seconds: Option<i64>,
+  log_level: Option<String>,
+  log_format: Option<String>,
+  timezone: Option<String>,
+  http_addr: Option<String>,
+  admin_log_channel_id: Option<u64>,
+  migrate_unprefixed_keys: Option<bool>,
+}
+
+impl Config {
+  pub fn load() -> Result<Self, BotError> {
+    // .envがなくても環境変数だけで起動できるようにする
+    match dotenv() {
+      Ok(_) => {}
+      Err(dotenv::Error::Io(e)) if e.kind() == ErrorKind::NotFound => {}
+      Err(e) => return Err(e.into()),
+    }
+    let file = match var("CONFIG_FILE") {
+      Some(path) => FileConfig::read(&path)?,
+      None if Path::new(DEFAULT_CONFIG_FILE).exists() => FileConfig::read(DEFAULT_CONFIG_FILE)?,
+      None => FileConfig::default(),
+    };
+    let token = secret("TOKEN")?.or(file.token).ok_or(missing("TOKEN"))?;
+    let channel_id = parse::<u64>("CHANNEL_ID")?.or(file.channel_id).ok_or(missing("CHANNEL_ID"))?;
+    let bot_id = parse::<u64>("BOT_ID")?.or(file.bot_id).ok_or(missing("BOT_ID"))?;
+    // REDIS_URLがなければ従来通りREDIS_PASSでローカルのRedisに接続する
+    let redis_url = match secret("REDIS_URL")?.or(file.redis_url) {
+      Some(url) => url,
+      None => {
+        let pass = secret("REDIS_PASS")?.or(file.redis_pass).ok_or(missing("REDIS_URL または REDIS_PASS"))?;
+        format!("redis://:{}@127.0.0.1/", pass)
+      }
+    };
+    let redis_key_prefix = var("REDIS_KEY_PREFIX").or(file.redis_key_prefix).unwrap_or(DEFAULT_REDIS_KEY_PREFIX.to_string());
+    let base_img_url = var("BASE_IMG_URL").or(file.base_img_url).ok_or(missing("BASE_IMG_URL"))?;
+    let expiry_seconds = parse::<i64>("EXPIRY_SECONDS")?.or(file.expiry_seconds).unwrap_or(DEFAULT_EXPIRY_SECONDS);
+    // RUST_LOGがあればLOG_LEVELより優先する
+    let log_filter = var("RUST_LOG").or(var("LOG_LEVEL")).or(file.log_level).unwrap_or(DEFAULT_LOG_FILTER.to_string());
+    let log_format = match var("LOG_FORMAT").or(file.log_format) {
+      Some(format) => LogFormat::from_str(&format).map_err(|_| invalid("LOG_FORMAT", &format))?,
+      None => LogFormat::Text,
+    };
+    let timezone = match var("TIMEZONE").or(file.timezone) {
+      Some(timezone) => Tz::from_str(&timezone).map_err(|_| invalid("TIMEZONE", &timezone))?,
+      None => DEFAULT_TIMEZONE,
+    };
+    let http_addr = var("HTTP_ADDR").or(file.http_addr).unwrap_or(DEFAULT_HTTP_ADDR.to_string());
+    let http_addr = SocketAddr::from_str(&http_addr).map_err(|_| invalid("HTTP_ADDR", &http_addr))?;
+    let admin_log_channel_id = parse::<u64>("ADMIN_LOG_CHANNEL_ID")?.or(file.admin_log_channel_id);
+    let migrate_unprefixed_keys = parse::<bool>("MIGRATE_UNPREFIXED_KEYS")?.or(file.migrate_unprefixed_keys).unwrap_or(false);
+    if channel_id == 0 {
+      return Err(invalid("CHANNEL_ID", &channel_id));
+    }
+    if bot_id == 0 {
+      return Err(invalid("BOT_ID", &bot_id));
+    }
+    if admin_log_channel_id == Some(0) {
+      return Err(invalid("ADMIN_LOG_CHANNEL_ID", &0));
+    }
+    // キーのレイアウトで区切り文字として使うため
+    if redis_key_prefix.is_empty() || redis_key_prefix.contains(':') {
+      return Err(invalid("REDIS_KEY_PREFIX", &redis_key_prefix));
+    }
+    if expiry_seconds <= 0 {
+      return Err(invalid("EXPIRY_SECONDS", &expiry_seconds));
+    }
+    if EnvFilter::try_new(&log_filter).is_err() {
+      return Err(invalid("RUST_LOG / LOG_LEVEL", &log_filter));
+    }
+    Ok(Self {
+      token,
+      channel_id: ChannelId::new(channel_id),
+      bot_id: UserId::new(bot_id),
+      redis_url,
+      redis_key_prefix,
+      base_img_url,
+      expiry_seconds,
+      log_filter,
+      log_format,
+      timezone,
+      http_addr,
+      admin_log_channel_id: admin_log_channel_id.map(ChannelId::new),
+      migrate_unprefixed_keys,
+    })
+  }
+}
+
+impl FileConfig {
+  fn read(path: &str) -> Result<Self, BotError> {
+    let content = std::fs::read_to_string(path)
+      .map_err(|e| BotError::InvalidConfig(format!("{} を読み込めません: {}", path, e)))?;
+    toml::from_str(&content)
+      .map_err(|e| BotError::InvalidConfig(format!("{} の形式が不正です: {}", path, e)))
+  }
+}
+
+// 空文字列は未設定として扱う
+fn var(key: &str) -> Option<String> {
+  dotenv::var(key).ok().filter(|v| !v.is_empty())
+}
+
+// KEY が未設定なら KEY_FILE に指定されたファイルの内容を使う (Docker/Kubernetesのシークレット)
+fn secret(key: &str) -> Result<Option<String>, BotError> {
+  if let Some(value) = var(key) {
+    return Ok(Some(value));
+  }
+  let file_key = format!("{}_FILE", key);
+  let Some(path) = var(&file_key) else {
+    return Ok(None);
+  };
+  let content = std::fs::read_to_string(&path)
+    .map_err(|e| BotError::InvalidConfig(format!("{} ({}) を読み込めません: {}", file_key, path, e)))?;
+  let value = content.trim_end_matches(['\r', '\n']).to_string();
+  Ok(Some(value).filter(|v| !v.is_empty()))
+}
+
+fn parse<T: FromStr>(key: &str) -> Result<Option<T>, BotError> {
+  var(key)
+    .map(|v| v.parse().map_err(|_| invalid(key, &v)))
+    .transpose()
+}
+
+fn missing(key: &str) -> BotError {
+  BotError::InvalidConfig(format!("{} が設定されていません", key))
 }
 
-pub fn get(key: &str) -> Result<String, BotError> {
-    let value = dotenv::var(key)?;
-    Ok(value)
+fn invalid(key: &str, value: &dyn Display) -> BotError {
+  BotError::InvalidConfig(format!("{} の値が不正です: {}", key, value))
 }
@@ -1,17 +1,25 @@
 use std::num::ParseIntError;
 
 use redis::RedisError;
+use serenity::all::InteractionId;
 use thiserror::Error;
 use tracing::subscriber::SetGlobalDefaultError;
 
+use crate::{bot::locale::{Locale, Text}, metrics::METRICS};
+
 #[derive(Debug, Error)]
 pub enum BotError {
   #[error("[BotError::DbError] {0}")]
   DbError(#[from] RedisError),
+  #[error("[BotError::RedisConnectError] {0} に接続できません: {1}")]
+  RedisConnectError(String, RedisError),
   #[error("[BotError::ConfigError] {0}")]
   ConfigError(#[from] dotenv::Error),
+  #[error("[BotError::InvalidConfig] {0}")]
+  InvalidConfig(String),
+  // serenity::Error は大きいため、Result を小さく保つよう Box に入れる
   #[error("[BotError::SerenityError] {0}")]
-  SerenityError(#[from] serenity::Error),
+  SerenityError(Box<serenity::Error>),
   #[error("[BotError::PinMessageError] {0}")]
   PinMessageError(#[from] ParseIntError),
   #[error("[BotError::TracingError] {0}")]
@@ -20,6 +28,65 @@ pub enum BotError {
   WebhookDataNotFound,
   #[error("[BotError::ComponentInteractionNotFound] コンポーネントが見つかりません")]
   ComponentInteractionNotFound,
+  #[error("[BotError::PresetNotFound] プリセットが見つかりません")]
+  PresetNotFound,
+  #[error("[BotError::SessionNotFound] 募集作成のセッションが見つかりません")]
+  SessionNotFound,
   #[error("[BotError::EmbedBroken] Embedが壊れています {0}")]
   EmbedBroken(&'static str),
+  #[error("[BotError::HttpServerError] {0}")]
+  HttpServerError(#[from] hyper::Error),
+  #[error("[BotError::SignalError] シグナルを受け取れません: {0}")]
+  SignalError(std::io::Error),
+}
+
+impl From<serenity::Error> for BotError {
+  fn from(e: serenity::Error) -> Self {
+    BotError::SerenityError(Box::new(e))
+  }
+}
+
+impl BotError {
+  // メトリクスのラベルに使う
+  pub fn variant(&self) -> &'static str {
+    match self {
+      BotError::DbError(_) => "DbError",
+      BotError::RedisConnectError(..) => "RedisConnectError",
+      BotError::ConfigError(_) => "ConfigError",
+      BotError::InvalidConfig(_) => "InvalidConfig",
+      BotError::SerenityError(_) => "SerenityError",
+      BotError::PinMessageError(_) => "PinMessageError",
+      BotError::TracingError(_) => "TracingError",
+      BotError::WebhookDataNotFound => "WebhookDataNotFound",
+      BotError::ComponentInteractionNotFound => "ComponentInteractionNotFound",
+      BotError::PresetNotFound => "PresetNotFound",
+      BotError::SessionNotFound => "SessionNotFound",
+      BotError::EmbedBroken(_) => "EmbedBroken",
+      BotError::HttpServerError(_) => "HttpServerError",
+      BotError::SignalError(_) => "SignalError",
+    }
+  }
+  // ユーザーに返信する文言。variantをエラーコード、インタラクションIDを相関IDとして添え、
+  // 問い合わせ時にログと照合できるようにする
+  pub fn user_message(&self, locale: Locale, correlation: InteractionId) -> String {
+    let text = match self {
+      BotError::DbError(_) | BotError::RedisConnectError(..) => Text::ErrorDatabase,
+      BotError::SerenityError(_) => Text::ErrorDiscord,
+      BotError::WebhookDataNotFound => Text::ErrorRecruitmentNotFound,
+      BotError::ComponentInteractionNotFound | BotError::SessionNotFound => Text::SessionExpired,
+      BotError::PresetNotFound => Text::ErrorPresetNotFound,
+      BotError::EmbedBroken(_) | BotError::PinMessageError(_) => Text::ErrorPanelBroken,
+      BotError::ConfigError(_)
+      | BotError::InvalidConfig(_)
+      | BotError::TracingError(_)
+      | BotError::HttpServerError(_)
+      | BotError::SignalError(_) => Text::ErrorInternal,
+    };
+    Text::ErrorCode.format(locale, &[text.get(locale), self.variant(), &correlation.get().to_string()])
+  }
+  // 警告としてログに出力し、発生数を記録する
+  pub fn log(&self, message: &str) {
+    METRICS.errors.inc(&[self.variant()]);
+    tracing::warn!(error = %self, variant = self.variant(), "{}", message);
+  }
 }
@@ -0,0 +1,61 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+
+use serde::Serialize;
+
+use crate::bot::types::RedisClient;
+
+// 各チェックのタイムアウト
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Gatewayの接続状態。Handlerのイベントで更新する
+#[derive(Default)]
+pub struct GatewayState {
+  connected: AtomicBool,
+}
+
+impl GatewayState {
+  pub fn set_connected(&self, connected: bool) {
+    self.connected.store(connected, Ordering::Relaxed);
+  }
+  pub fn is_connected(&self) -> bool {
+    self.connected.load(Ordering::Relaxed)
+  }
+}
+
+// /healthz と /readyz で使う
+pub struct Health {
+  pub gateway: Arc<GatewayState>,
+  pub redis_client: RedisClient,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+  pub ready: bool,
+  pub gateway: bool,
+  pub redis: bool,
+  // 募集用のWebhookを保存済みか。初回の募集で作成するため、readyには含めない
+  pub webhook: bool,
+}
+
+impl Health {
+  // 募集を受け付けられる状態か
+  // プローブのたびにDiscord APIを呼ばないよう、RedisとGatewayの状態だけを確認する
+  pub async fn check(&self) -> Report {
+    let gateway = self.gateway.is_connected();
+    let redis = matches!(
+      tokio::time::timeout(CHECK_TIMEOUT, self.redis_client.ping()).await,
+      Ok(Ok(_))
+    );
+    // Redisに接続できなければWebhookのURLも取得できない
+    let webhook = redis && matches!(
+      tokio::time::timeout(CHECK_TIMEOUT, self.redis_client.has_webhook_url()).await,
+      Ok(Ok(true))
+    );
+    Report {
+      ready: gateway && redis,
+      gateway,
+      redis,
+      webhook,
+    }
+  }
+}
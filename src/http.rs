@@ -0,0 +1,44 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use hyper::{header::CONTENT_TYPE, service::{make_service_fn, service_fn}, Body, Method, Request, Response, Server, StatusCode};
+
+use crate::{error::BotError, health::Health, metrics::METRICS};
+
+// 監視用のHTTPサーバーを起動する。ポートを確保できなければ起動時にエラーにする
+pub fn start(addr: SocketAddr, health: Arc<Health>) -> Result<(), BotError> {
+  let make_service = make_service_fn(move |_| {
+    let health = health.clone();
+    async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, health.clone()))) }
+  });
+  let server = Server::try_bind(&addr)?.serve(make_service);
+  tracing::info!(%addr, "HTTP server listening");
+  tokio::spawn(async move {
+    if let Err(e) = server.await {
+      tracing::error!(error = %e, "HTTP server stopped");
+    }
+  });
+  Ok(())
+}
+
+async fn handle(request: Request<Body>, health: Arc<Health>) -> Result<Response<Body>, Infallible> {
+  let response = match (request.method(), request.uri().path()) {
+    (&Method::GET, "/metrics") => Response::builder()
+      .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+      .body(Body::from(METRICS.render())),
+    // プロセスが応答できれば生存とみなす。Gatewayの再接続はserenityに任せる
+    (&Method::GET, "/healthz") => Response::builder()
+      .header(CONTENT_TYPE, "application/json")
+      .body(Body::from(format!("{{\"gateway\":{}}}", health.gateway.is_connected()))),
+    (&Method::GET, "/readyz") => {
+      let report = health.check().await;
+      Response::builder()
+        .status(if report.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE })
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(&report).unwrap_or_default()))
+    }
+    _ => Response::builder()
+      .status(StatusCode::NOT_FOUND)
+      .body(Body::empty()),
+  };
+  Ok(response.unwrap_or_default())
+}
@@ -1,50 +1,105 @@
 mod bot;
 mod config;
 mod error;
+mod health;
+mod http;
+mod metrics;
+mod shutdown;
 
 use std::{collections::HashMap,sync::Arc};
 
+use chrono::SecondsFormat;
+use chrono_tz::Tz;
 use error::BotError;
 use bot::Handler;
-use serenity::all::GatewayIntents;
+use serenity::all::{GatewayIntents, Http};
 use tokio::sync::Mutex;
-use tracing::{Level, instrument};
-use tracing_subscriber::fmt::time::FormatTime;
+use tracing::instrument;
+use tracing_subscriber::{fmt::time::FormatTime, EnvFilter};
 
-use crate::bot::types::RedisClient;
+use crate::{bot::{admin_log::AdminLog, panels::EntryDebounce, types::RedisClient}, config::{Config, LogFormat}, health::{GatewayState, Health}, shutdown::Shutdown};
 
 #[tokio::main(flavor = "multi_thread")]
 #[instrument(name = "main", err)]
 async fn main() -> Result<(), BotError> {
-  let logger = tracing_subscriber::fmt::Subscriber::builder()
-    .with_max_level(Level::INFO)
-    .with_timer(JapanStandardTime)
-    .finish();
-  tracing::subscriber::set_global_default(logger)?;
-  config::load()?;
-  let token = config::get("TOKEN")?;
+  let config = Config::load()?;
+  // フィルタはConfig::loadで検証済み
+  let logger = tracing_subscriber::fmt()
+    .with_env_filter(EnvFilter::new(&config.log_filter))
+    .with_timer(LocalTime(config.timezone));
+  match config.log_format {
+    LogFormat::Text => tracing::subscriber::set_global_default(logger.finish())?,
+    LogFormat::Json => tracing::subscriber::set_global_default(logger.json().flatten_event(true).finish())?,
+  }
+  // キーの名前空間に使うため、募集チャンネルのサーバーを先に取得する
+  let http = Http::new(&config.token);
+  let guild = config
+    .channel_id
+    .to_channel(&http)
+    .await?
+    .guild()
+    .ok_or_else(|| BotError::InvalidConfig("CHANNEL_ID はサーバーのチャンネルではありません".to_string()))?
+    .guild_id;
+  let redis_client = RedisClient::new(&config, guild).await?;
+  bot::migration::run(&redis_client, config.migrate_unprefixed_keys).await?;
+  // `valo-member-bot migrate` はマイグレーションのみ実行して終了する
+  if std::env::args().nth(1).as_deref() == Some("migrate") {
+    return Ok(());
+  }
+  let gateway = Arc::new(GatewayState::default());
+  let shutdown = Arc::new(Shutdown::default());
   let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
   let handler = Handler {
     question_state: Arc::new(Mutex::new(HashMap::new())),
     component_store: Arc::new(Mutex::new(HashMap::new())),
-    redis_client: Arc::new(Mutex::new(RedisClient::new(&config::get("REDIS_PASS")?).await?)),
+    redis_client: redis_client.clone(),
+    config: Arc::new(config.clone()),
+    gateway: gateway.clone(),
+    shutdown: shutdown.clone(),
+    admin_log: Arc::new(AdminLog::new(config.admin_log_channel_id)),
+    entry_debounce: Arc::new(EntryDebounce::default()),
   };
-  let mut client = serenity::Client::builder(token, intents)
-    .event_handler_arc(Arc::new(handler))
+  match handler.restore_question_state().await {
+    Ok(restored) => tracing::info!(restored, "Question state restored"),
+    Err(e) => e.log("Failed to restore question state"),
+  }
+  let handler = Arc::new(handler);
+  let mut client = serenity::Client::builder(&config.token, intents)
+    .event_handler_arc(handler.clone())
     .await?;
+  http::start(config.http_addr, Arc::new(Health {
+    gateway,
+    redis_client,
+  }))?;
+  let shard_manager = client.shard_manager.clone();
+  tokio::spawn(async move {
+    if let Err(e) = shutdown::wait_for_signal().await {
+      e.log("Failed to listen for shutdown signal");
+      return;
+    }
+    tracing::info!("Shutting down");
+    shutdown.drain().await;
+    match handler.persist_question_state().await {
+      Ok(persisted) => tracing::info!(persisted, "Question state persisted"),
+      Err(e) => e.log("Failed to persist question state"),
+    }
+    shard_manager.shutdown_all().await;
+  });
   client.start().await?;
+  // Redisの接続は /readyz と共有しているため、mainを抜けてランタイムが終了するときに閉じる
+  tracing::info!("Shut down");
   Ok(())
 }
 
-struct JapanStandardTime;
+// 設定したタイムゾーンの時刻をオフセット付きで出力する
+struct LocalTime(Tz);
 
-impl FormatTime for JapanStandardTime {
+impl FormatTime for LocalTime {
   fn format_time(
     &self,
     w: &mut tracing_subscriber::fmt::format::Writer<'_>,
   ) -> std::fmt::Result {
-    let utc_now = chrono::Utc::now();
-    let jst_now = utc_now + chrono::Duration::hours(9);
-    write!(w, "{}", jst_now.format("%Y-%m-%d %H:%M:%S"))
+    let now = chrono::Utc::now().with_timezone(&self.0);
+    write!(w, "{}", now.to_rfc3339_opts(SecondsFormat::Millis, false))
   }
 }
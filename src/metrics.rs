@@ -0,0 +1,195 @@
+use std::{collections::BTreeMap, fmt::Write, future::Future, pin::Pin, sync::{LazyLock, Mutex}, time::Instant};
+
+use redis::{aio::{ConnectionLike, ConnectionManager}, Cmd, Pipeline, RedisFuture, Value};
+
+// /metrics で公開するメトリクス
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+const REDIS_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+const DISCORD_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub struct Metrics {
+  pub recruitments: CounterVec,
+  pub joins: CounterVec,
+  pub leaves: CounterVec,
+  pub deletes: CounterVec,
+  pub closes: CounterVec,
+  pub fills: CounterVec,
+  pub expiries: CounterVec,
+  pub errors: CounterVec,
+  pub redis_duration: HistogramVec,
+  pub discord_duration: HistogramVec,
+}
+
+impl Metrics {
+  fn new() -> Self {
+    Self {
+      recruitments: CounterVec::new("valo_recruitments_created_total", "作成された募集の数", &["mode", "server"]),
+      joins: CounterVec::new("valo_joins_total", "参加ボタンの結果", &["result"]),
+      leaves: CounterVec::new("valo_leaves_total", "参加をやめるボタンの結果", &["result"]),
+      deletes: CounterVec::new("valo_deletes_total", "削除ボタンの結果", &["result"]),
+      closes: CounterVec::new("valo_closes_total", "締め切るボタンの結果", &["result"]),
+      fills: CounterVec::new("valo_recruitments_filled_total", "満員になった募集の数", &[]),
+      expiries: CounterVec::new("valo_recruitments_expired_total", "期限切れの募集が操作された数", &[]),
+      errors: CounterVec::new("valo_errors_total", "BotErrorの発生数", &["variant"]),
+      redis_duration: HistogramVec::new("valo_redis_command_duration_seconds", "Redisのコマンドの所要時間", &["command"], REDIS_BUCKETS),
+      discord_duration: HistogramVec::new("valo_discord_request_duration_seconds", "Discord APIの呼び出しの所要時間", &["operation"], DISCORD_BUCKETS),
+    }
+  }
+  // Prometheusのテキスト形式で出力する
+  pub fn render(&self) -> String {
+    let mut out = String::new();
+    for counter in [&self.recruitments, &self.joins, &self.leaves, &self.deletes, &self.closes, &self.fills, &self.expiries, &self.errors] {
+      counter.render(&mut out);
+    }
+    self.redis_duration.render(&mut out);
+    self.discord_duration.render(&mut out);
+    out
+  }
+}
+
+pub struct CounterVec {
+  name: &'static str,
+  help: &'static str,
+  labels: &'static [&'static str],
+  values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+  fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+    Self { name, help, labels, values: Mutex::new(BTreeMap::new()) }
+  }
+  // label_valuesはlabelsと同じ順序で渡す
+  pub fn inc(&self, label_values: &[&str]) {
+    let key = label_values.iter().map(|v| v.to_string()).collect();
+    *self.values.lock().unwrap().entry(key).or_insert(0) += 1;
+  }
+  fn render(&self, out: &mut String) {
+    let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+    let _ = writeln!(out, "# TYPE {} counter", self.name);
+    let values = self.values.lock().unwrap();
+    // ラベルのないカウンターは一度も増えていなくても0を出力する
+    if self.labels.is_empty() && values.is_empty() {
+      let _ = writeln!(out, "{} 0", self.name);
+    }
+    for (label_values, value) in values.iter() {
+      let _ = writeln!(out, "{}{} {}", self.name, format_labels(self.labels, label_values, None), value);
+    }
+  }
+}
+
+pub struct HistogramVec {
+  name: &'static str,
+  help: &'static str,
+  labels: &'static [&'static str],
+  buckets: &'static [f64],
+  values: Mutex<BTreeMap<Vec<String>, Histogram>>,
+}
+
+#[derive(Default)]
+struct Histogram {
+  // bucketsの各上限以下の観測数 (累積)
+  counts: Vec<u64>,
+  sum: f64,
+  count: u64,
+}
+
+impl HistogramVec {
+  fn new(name: &'static str, help: &'static str, labels: &'static [&'static str], buckets: &'static [f64]) -> Self {
+    Self { name, help, labels, buckets, values: Mutex::new(BTreeMap::new()) }
+  }
+  pub fn observe(&self, label_values: &[&str], seconds: f64) {
+    let key = label_values.iter().map(|v| v.to_string()).collect();
+    let mut values = self.values.lock().unwrap();
+    let histogram = values.entry(key).or_insert_with(|| Histogram {
+      counts: vec![0; self.buckets.len()],
+      ..Default::default()
+    });
+    for (count, &bound) in histogram.counts.iter_mut().zip(self.buckets) {
+      if seconds <= bound {
+        *count += 1;
+      }
+    }
+    histogram.sum += seconds;
+    histogram.count += 1;
+  }
+  fn render(&self, out: &mut String) {
+    let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+    let _ = writeln!(out, "# TYPE {} histogram", self.name);
+    for (label_values, histogram) in self.values.lock().unwrap().iter() {
+      for (count, bound) in histogram.counts.iter().zip(self.buckets) {
+        let le = bound.to_string();
+        let _ = writeln!(out, "{}_bucket{} {}", self.name, format_labels(self.labels, label_values, Some(&le)), count);
+      }
+      let _ = writeln!(out, "{}_bucket{} {}", self.name, format_labels(self.labels, label_values, Some("+Inf")), histogram.count);
+      let _ = writeln!(out, "{}_sum{} {}", self.name, format_labels(self.labels, label_values, None), histogram.sum);
+      let _ = writeln!(out, "{}_count{} {}", self.name, format_labels(self.labels, label_values, None), histogram.count);
+    }
+  }
+}
+
+fn format_labels(labels: &[&str], values: &[String], le: Option<&str>) -> String {
+  let mut pairs: Vec<String> = labels
+    .iter()
+    .zip(values)
+    .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+    .collect();
+  if let Some(le) = le {
+    pairs.push(format!("le=\"{}\"", le));
+  }
+  if pairs.is_empty() {
+    String::new()
+  } else {
+    format!("{{{}}}", pairs.join(","))
+  }
+}
+
+fn escape(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Discord APIの呼び出しにかかった時間を記録する
+pub async fn time_discord<F: Future>(operation: &'static str, future: F) -> F::Output {
+  let start = Instant::now();
+  let output = future.await;
+  METRICS.discord_duration.observe(&[operation], start.elapsed().as_secs_f64());
+  output
+}
+
+// すべてのコマンドの所要時間を記録するConnectionManagerのラッパー
+#[derive(Clone)]
+pub struct TimedConnection(pub ConnectionManager);
+
+impl ConnectionLike for TimedConnection {
+  fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+    // 先頭の引数がコマンド名
+    let command = cmd
+      .args_iter()
+      .next()
+      .and_then(|arg| match arg {
+        redis::Arg::Simple(name) => std::str::from_utf8(name).ok(),
+        redis::Arg::Cursor => None,
+      })
+      .unwrap_or("UNKNOWN")
+      .to_ascii_uppercase();
+    let future = self.0.req_packed_command(cmd);
+    Box::pin(async move {
+      let start = Instant::now();
+      let result = future.await;
+      METRICS.redis_duration.observe(&[&command], start.elapsed().as_secs_f64());
+      result
+    })
+  }
+  fn req_packed_commands<'a>(&'a mut self, cmd: &'a Pipeline, offset: usize, count: usize) -> RedisFuture<'a, Vec<Value>> {
+    let future: Pin<Box<_>> = self.0.req_packed_commands(cmd, offset, count);
+    Box::pin(async move {
+      let start = Instant::now();
+      let result = future.await;
+      METRICS.redis_duration.observe(&["PIPELINE"], start.elapsed().as_secs_f64());
+      result
+    })
+  }
+  fn get_db(&self) -> i64 {
+    self.0.get_db()
+  }
+}
@@ -0,0 +1,71 @@
+use std::{sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}, time::Duration};
+
+use tokio::sync::Notify;
+
+use crate::error::BotError;
+
+// 処理中のイベントを待つ上限
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// 停止処理中かどうかと、処理中のイベントの数を管理する
+#[derive(Default)]
+pub struct Shutdown {
+  stopping: AtomicBool,
+  in_flight: AtomicUsize,
+  idle: Notify,
+}
+
+// 処理中のイベント。dropで処理の終了を通知する
+pub struct InFlight(Arc<Shutdown>);
+
+impl Shutdown {
+  // 停止処理中ならNoneを返し、新しいイベントを受け付けない
+  pub fn enter(self: &Arc<Self>) -> Option<InFlight> {
+    self.in_flight.fetch_add(1, Ordering::SeqCst);
+    let guard = InFlight(self.clone());
+    if self.stopping.load(Ordering::SeqCst) {
+      return None;
+    }
+    Some(guard)
+  }
+  // 新しいイベントの受け付けを止め、処理中のイベントが終わるのを待つ
+  pub async fn drain(&self) {
+    self.stopping.store(true, Ordering::SeqCst);
+    let wait = async {
+      loop {
+        let idle = self.idle.notified();
+        if self.in_flight.load(Ordering::SeqCst) == 0 {
+          return;
+        }
+        idle.await;
+      }
+    };
+    if tokio::time::timeout(DRAIN_TIMEOUT, wait).await.is_err() {
+      tracing::warn!(in_flight = self.in_flight.load(Ordering::SeqCst), "Timed out waiting for in-flight events");
+    }
+  }
+}
+
+impl Drop for InFlight {
+  fn drop(&mut self) {
+    if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+      self.0.idle.notify_waiters();
+    }
+  }
+}
+
+// SIGTERM または SIGINT (Ctrl+C) を待つ
+pub async fn wait_for_signal() -> Result<(), BotError> {
+  #[cfg(unix)]
+  {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut terminate = signal(SignalKind::terminate()).map_err(BotError::SignalError)?;
+    tokio::select! {
+      _ = terminate.recv() => {}
+      result = tokio::signal::ctrl_c() => result.map_err(BotError::SignalError)?,
+    }
+  }
+  #[cfg(not(unix))]
+  tokio::signal::ctrl_c().await.map_err(BotError::SignalError)?;
+  Ok(())
+}